@@ -0,0 +1,156 @@
+use std::time::Instant;
+
+use memegeom::{
+    geom::distance::pt_pt_dist,
+    primitive::{point::Pt, pt},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    config::{Annealing, Schedule},
+    model::problem::{Problem, Solution},
+    progress::Progress,
+    scoring::{ScoreState, ScoringRules, BOUND_MIN_DIST},
+    stop,
+    validation::ensure_complete,
+};
+
+// Probabilities of the move kinds, the rest are shifts
+const SWAP_RATE: f64 = 0.2;
+const TELEPORT_RATE: f64 = 0.05;
+
+enum Move {
+    Shift(usize, Pt),
+    Teleport(usize, Pt),
+    Swap(usize, usize),
+}
+
+/// Simulated annealing over musician positions, starting from `solution`.
+///
+/// Every move keeps the placement valid: shifted and teleported musicians stay inside the stage
+/// margin and at least `BOUND_MIN_DIST` from the others, swaps exchange musicians of different
+/// instruments. Returns the best solution seen.
+pub fn anneal(
+    task_id: usize,
+    rules: ScoringRules,
+    prob: &Problem,
+    solution: &Solution,
+    params: &Annealing,
+    progress: Progress,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
+    let n = prob.musicians.len();
+    let mut rng = StdRng::seed_from_u64(params.seed + task_id as u64);
+    let mut state = ScoreState::new(rules, prob, solution);
+    let mut best = solution.clone();
+    let mut best_score = state.score();
+    let (left, bottom, right, top) = (
+        prob.stage_bottom_left[0] + BOUND_MIN_DIST,
+        prob.stage_bottom_left[1] + BOUND_MIN_DIST,
+        prob.stage_bottom_left[0] + prob.stage_width - BOUND_MIN_DIST,
+        prob.stage_bottom_left[1] + prob.stage_height - BOUND_MIN_DIST,
+    );
+    let has_swaps = prob.musicians.iter().any(|i| *i != prob.musicians[0]);
+    log::info!(
+        "task={task_id} annealing initial score={best_score} seed={}",
+        params.seed + task_id as u64
+    );
+    if n == 0 {
+        return Ok(best);
+    }
+
+    let start = Instant::now();
+    let mut n_accepted = 0u64;
+    for it in 1..=params.max_iters {
+        let elapsed = start.elapsed().as_secs_f64();
+        if elapsed > params.max_secs as f64 {
+            log::info!("task={task_id} iter={it} annealing time limit reached");
+            break;
+        }
+        if it % 1000 == 0 && stop::requested() {
+            log::info!("task={task_id} iter={it} annealing stop requested");
+            break;
+        }
+        let fraction = (elapsed / params.max_secs as f64)
+            .max(it as f64 / params.max_iters as f64)
+            .min(1.0);
+        let temperature = match params.schedule {
+            Schedule::Linear => params.t_start + (params.t_end - params.t_start) * fraction,
+            Schedule::Geometric => params.t_start * (params.t_end / params.t_start).powf(fraction),
+        };
+
+        // without swaps the other kinds keep their proportions
+        let kind: f64 = if has_swaps {
+            rng.gen()
+        } else {
+            rng.gen_range(SWAP_RATE..1.0)
+        };
+        let mv = if kind < SWAP_RATE {
+            let first = rng.gen_range(0..n);
+            let second = rng.gen_range(0..n);
+            if prob.musicians[first] == prob.musicians[second] {
+                continue;
+            }
+            Move::Swap(first, second)
+        } else if kind < SWAP_RATE + TELEPORT_RATE {
+            let idx = rng.gen_range(0..n);
+            let p = pt(
+                left + rng.gen::<f64>() * (right - left),
+                bottom + rng.gen::<f64>() * (top - bottom),
+            );
+            Move::Teleport(idx, p)
+        } else {
+            let idx = rng.gen_range(0..n);
+            let old = state.position(idx);
+            let p = pt(
+                (old.x + rng.gen_range(-params.shift..=params.shift)).clamp(left, right),
+                (old.y + rng.gen_range(-params.shift..=params.shift)).clamp(bottom, top),
+            );
+            Move::Shift(idx, p)
+        };
+
+        let old_score = state.score();
+        let undo = match mv {
+            Move::Shift(idx, p) | Move::Teleport(idx, p) => {
+                if !is_free(&state, n, idx, &p) {
+                    continue;
+                }
+                let old = state.position(idx);
+                state.move_musician(idx, p);
+                Move::Shift(idx, old)
+            }
+            Move::Swap(first, second) => {
+                state.swap_musicians(first, second);
+                Move::Swap(first, second)
+            }
+        };
+        let delta = state.score() - old_score;
+        if delta >= 0.0 || rng.gen::<f64>() < (delta / temperature).exp() {
+            n_accepted += 1;
+            if state.score() > best_score {
+                best_score = state.score();
+                best = state.to_solution();
+            }
+        } else {
+            match undo {
+                Move::Shift(idx, p) | Move::Teleport(idx, p) => state.move_musician(idx, p),
+                Move::Swap(first, second) => state.swap_musicians(first, second),
+            }
+        }
+        if it % 10000 == 0 {
+            log::info!(
+                "task={task_id} iter={it} temperature={temperature} score={} best_score={best_score} accepted={n_accepted}",
+                state.score()
+            );
+            progress.report(task_id, &best, best_score);
+        }
+    }
+    log::info!("task={task_id} annealing best_score={best_score}");
+    Ok(best)
+}
+
+fn is_free(state: &ScoreState, n: usize, musician_idx: usize, p: &Pt) -> bool {
+    (0..n).all(|other_idx| {
+        other_idx == musician_idx || pt_pt_dist(p, &state.position(other_idx)) >= BOUND_MIN_DIST
+    })
+}
@@ -0,0 +1,191 @@
+use memegeom::primitive::{point::Pt, pt};
+
+use crate::{
+    model::problem::{Problem, Solution},
+    scoring::{evaluate_exact, pt_to_pos, ScoringRules, IMPACT_SCALING_COEF},
+    shadow::ShadowMap,
+};
+
+/// Value of every instrument at every slot when no musician blocks: the sum of
+/// `ceil(IMPACT_SCALING_COEF * taste / d²)` over all attendees, i.e. the score at volume 1.
+/// With `shadows`, attendees hidden from a slot by pillars don't count. Indexed as
+/// `[instrument][slot]`.
+pub fn instrument_slot_values(
+    problem: &Problem,
+    slots: &[Pt],
+    shadows: Option<&ShadowMap>,
+) -> Vec<Vec<f64>> {
+    let n_instruments = problem
+        .musicians
+        .iter()
+        .map(|i| *i as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut values = vec![vec![0.0; slots.len()]; n_instruments];
+    for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+        let a = pt(attendee.x, attendee.y);
+        for (slot_idx, slot) in slots.iter().enumerate() {
+            if shadows.is_some_and(|shadows| !shadows.is_visible(slot, attendee_idx)) {
+                continue;
+            }
+            let d2 = (slot.x - a.x).powi(2) + (slot.y - a.y).powi(2);
+            for (instrument, row) in values.iter_mut().enumerate() {
+                row[slot_idx] += (IMPACT_SCALING_COEF * attendee.tastes[instrument] / d2).ceil();
+            }
+        }
+    }
+    values
+}
+
+/// Puts every musician on one of the slots so that the total score without blocking and
+/// closeness factors is maximal, then scores the result exactly.
+///
+/// Musicians that would lose points are counted as zero, as the volume pass silences them.
+pub fn assign(
+    rules: ScoringRules,
+    problem: &Problem,
+    slots: &[Pt],
+) -> anyhow::Result<(Solution, f64)> {
+    if slots.len() < problem.musicians.len() {
+        anyhow::bail!(
+            "{} slots are not enough for {} musicians",
+            slots.len(),
+            problem.musicians.len()
+        );
+    }
+    let values = instrument_slot_values(problem, slots, None);
+    let costs: Vec<Vec<f64>> = problem
+        .musicians
+        .iter()
+        .map(|instrument| {
+            values[*instrument as usize]
+                .iter()
+                .map(|v| -v.max(0.0))
+                .collect()
+        })
+        .collect();
+    let slot_of = min_cost_assignment(&costs);
+    let solution = Solution::new(
+        slot_of
+            .into_iter()
+            .map(|slot_idx| pt_to_pos(&slots[slot_idx]))
+            .collect(),
+    );
+    let score = evaluate_exact(rules, problem, &solution);
+    Ok((solution, score))
+}
+
+/// Hungarian algorithm: for every row the column assigned to it, so that every column is used at
+/// most once and the total cost is minimal. Needs at least as many columns as rows, O(R²·C).
+pub fn min_cost_assignment(costs: &[Vec<f64>]) -> Vec<usize> {
+    let n_rows = costs.len();
+    if n_rows == 0 {
+        return vec![];
+    }
+    let n_cols = costs[0].len();
+    assert!(n_rows <= n_cols, "more rows than columns");
+    // potentials and matching are 1-based, row 0 and column 0 are the fake ones of the augmentation
+    let mut u = vec![0.0; n_rows + 1];
+    let mut v = vec![0.0; n_cols + 1];
+    let mut row_of = vec![0; n_cols + 1];
+    let mut way = vec![0; n_cols + 1];
+    for row in 1..=n_rows {
+        row_of[0] = row;
+        let mut col0 = 0;
+        let mut min_v = vec![f64::INFINITY; n_cols + 1];
+        let mut used = vec![false; n_cols + 1];
+        loop {
+            used[col0] = true;
+            let row0 = row_of[col0];
+            let mut delta = f64::INFINITY;
+            let mut col1 = 0;
+            for col in 1..=n_cols {
+                if used[col] {
+                    continue;
+                }
+                let cur = costs[row0 - 1][col - 1] - u[row0] - v[col];
+                if cur < min_v[col] {
+                    min_v[col] = cur;
+                    way[col] = col0;
+                }
+                if min_v[col] < delta {
+                    delta = min_v[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..=n_cols {
+                if used[col] {
+                    u[row_of[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_v[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if row_of[col0] == 0 {
+                break;
+            }
+        }
+        while col0 != 0 {
+            let col1 = way[col0];
+            row_of[col0] = row_of[col1];
+            col0 = col1;
+        }
+    }
+    let mut col_of = vec![0; n_rows];
+    for col in 1..=n_cols {
+        if row_of[col] != 0 {
+            col_of[row_of[col] - 1] = col - 1;
+        }
+    }
+    col_of
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::assignment::min_cost_assignment;
+
+    fn brute_force(costs: &[Vec<f64>], row: usize, used: &mut Vec<bool>) -> f64 {
+        if row == costs.len() {
+            return 0.0;
+        }
+        let mut best = f64::INFINITY;
+        for col in 0..used.len() {
+            if !used[col] {
+                used[col] = true;
+                best = best.min(costs[row][col] + brute_force(costs, row + 1, used));
+                used[col] = false;
+            }
+        }
+        best
+    }
+
+    #[test]
+    pub fn hungarian_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let n_rows = rng.gen_range(1..=5);
+            let n_cols = rng.gen_range(n_rows..=7);
+            let costs: Vec<Vec<f64>> = (0..n_rows)
+                .map(|_| {
+                    (0..n_cols)
+                        .map(|_| rng.gen_range(-100..100) as f64)
+                        .collect()
+                })
+                .collect();
+            let col_of = min_cost_assignment(&costs);
+            let mut cols = col_of.clone();
+            cols.sort();
+            cols.dedup();
+            assert_eq!(cols.len(), n_rows);
+            let total: f64 = col_of
+                .iter()
+                .enumerate()
+                .map(|(row, col)| costs[row][*col])
+                .sum();
+            assert_eq!(total, brute_force(&costs, 0, &mut vec![false; n_cols]));
+        }
+    }
+}
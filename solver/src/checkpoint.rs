@@ -0,0 +1,197 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::problem::Solution;
+
+/// Where one pipeline run of a checkpointed run is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaskProgress {
+    pub task_id: usize,
+    /// Pipeline steps finished, see `Checkpoint::steps`
+    pub steps: usize,
+}
+
+/// The best solution of a run so far, written periodically so that a killed run can be resumed.
+///
+/// Only whole pipeline steps are recorded: a resumed run starts every task from `solution` at
+/// `steps`, so the step that was interrupted runs again from its start with its full budget.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// File name of the problem
+    pub problem: String,
+    /// Pipeline spec of the run, a resumed run must use the same one
+    pub pipeline: String,
+    /// Seed of the first task, task `i` uses `rand_seed + i`
+    pub rand_seed: u64,
+    pub score: f64,
+    pub solution: Solution,
+    /// Pipeline steps finished by the task that found `solution`. A pipeline of `n` stages has
+    /// `2 * n` steps: `improve` of every stage, then `finalize` of every stage.
+    pub steps: usize,
+    pub tasks: Vec<TaskProgress>,
+}
+
+impl Checkpoint {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Writes to a temporary file first, so that a kill never leaves a truncated checkpoint.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+struct State {
+    best: Option<(Solution, f64, usize)>,
+    tasks: Vec<TaskProgress>,
+    last_write: Instant,
+}
+
+/// Collects the progress of all tasks of a run and writes a `Checkpoint` at most every
+/// `interval`. Optimizers report to it at their own pace, reporting is cheap between writes.
+pub struct Checkpointer {
+    path: PathBuf,
+    interval: Duration,
+    problem: String,
+    pipeline: String,
+    rand_seed: u64,
+    state: Mutex<State>,
+}
+
+impl Checkpointer {
+    pub fn new(
+        path: PathBuf,
+        interval: Duration,
+        problem: String,
+        pipeline: String,
+        rand_seed: u64,
+        n_tasks: usize,
+    ) -> Self {
+        let tasks = (0..n_tasks)
+            .map(|task_id| TaskProgress { task_id, steps: 0 })
+            .collect();
+        Self {
+            path,
+            interval,
+            problem,
+            pipeline,
+            rand_seed,
+            state: Mutex::new(State {
+                best: None,
+                tasks,
+                last_write: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records that the task has found `solution` in its current step.
+    pub fn progress(&self, task_id: usize, solution: &Solution, score: f64) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("checkpoint lock is never poisoned");
+        self.offer(&mut state, task_id, solution, score);
+    }
+
+    /// Records that the task has finished `steps` pipeline steps with `solution` as its best.
+    pub fn step_done(&self, task_id: usize, steps: usize, solution: &Solution, score: f64) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("checkpoint lock is never poisoned");
+        state.tasks[task_id].steps = steps;
+        self.offer(&mut state, task_id, solution, score);
+    }
+
+    /// Writes the checkpoint now, if any solution has been reported.
+    pub fn write(&self) -> anyhow::Result<()> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("checkpoint lock is never poisoned");
+        self.write_state(&mut state)
+    }
+
+    fn offer(&self, state: &mut State, task_id: usize, solution: &Solution, score: f64) {
+        if state.best.as_ref().is_none_or(|(_, best, _)| score > *best) {
+            let steps = state.tasks[task_id].steps;
+            state.best = Some((solution.clone(), score, steps));
+        }
+        if state.last_write.elapsed() >= self.interval {
+            if let Err(error) = self.write_state(state) {
+                log::warn!("can't write checkpoint {:?}: {error}", self.path);
+            }
+        }
+    }
+
+    fn write_state(&self, state: &mut State) -> anyhow::Result<()> {
+        state.last_write = Instant::now();
+        let Some((solution, score, steps)) = &state.best else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint {
+            problem: self.problem.clone(),
+            pipeline: self.pipeline.clone(),
+            rand_seed: self.rand_seed,
+            score: *score,
+            solution: solution.clone(),
+            steps: *steps,
+            tasks: state.tasks.clone(),
+        };
+        checkpoint.write(&self.path)?;
+        log::info!("checkpoint {:?} score={score} steps={steps}", self.path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        checkpoint::{Checkpoint, Checkpointer},
+        model::problem::{Position, Solution},
+    };
+
+    #[test]
+    pub fn best_solution_is_written_and_read_back() {
+        let path =
+            std::env::temp_dir().join(format!("checkpoint-test-{}.json", std::process::id()));
+        let checkpointer = Checkpointer::new(
+            path.clone(),
+            Duration::from_secs(3600),
+            "1.json".to_string(),
+            "random,descent,volume".to_string(),
+            5,
+            2,
+        );
+        let worse = Solution::new(vec![Position::new(10.0, 10.0)]);
+        let better = Solution::new(vec![Position::new(20.0, 10.0)]);
+        checkpointer.step_done(0, 1, &worse, 100.0);
+        checkpointer.progress(1, &better, 200.0);
+        checkpointer.step_done(0, 2, &worse, 150.0);
+        assert!(!path.exists(), "nothing is written before the interval");
+        checkpointer.write().unwrap();
+
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.problem, "1.json");
+        assert_eq!(checkpoint.rand_seed, 5);
+        assert_eq!(checkpoint.score, 200.0);
+        assert_eq!(checkpoint.solution.placements[0].x, 20.0);
+        assert_eq!(checkpoint.steps, 0);
+        assert_eq!(checkpoint.tasks[0].steps, 2);
+        assert_eq!(checkpoint.tasks[1].steps, 0);
+    }
+}
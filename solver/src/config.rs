@@ -2,11 +2,183 @@ use config::{File, FileFormat};
 use serde::Deserialize;
 use std::path::PathBuf;
 
+use crate::scoring::ScoringRules;
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Solver {
     pub problems: Directory,
     pub solutions: Directory,
+    /// Where every problem's checkpoint is written, under the problem's file name
+    #[serde(default)]
+    pub checkpoints: Option<Directory>,
     pub log: Log,
+    /// Overrides the scoring rules derived from the problem ids
+    #[serde(default)]
+    pub rules: Option<ScoringRules>,
+    #[serde(default)]
+    pub search: Search,
+}
+
+/// Random search and descent parameters, the same as the `problem` subcommand options
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Search {
+    pub rand_seed: u64,
+    pub rand_iters: u64,
+    pub rand_max_secs: u64,
+    pub descent_iters: u64,
+    pub descent_max_secs: u64,
+    /// Time budget of the swap pass after the descent
+    pub swap_max_secs: u64,
+    /// Time budget of the shield pass after the volumes are set
+    pub shield_max_secs: u64,
+    /// Threads shared by all problems
+    pub n_threads: usize,
+    pub n_seeds: usize,
+    /// Times every seed reruns its pipeline from a perturbed copy of the best solution of all seeds
+    pub restarts: u64,
+    /// Musicians moved to random spots before a restart
+    pub perturb_musicians: usize,
+    /// Time budget of every seed over all its restarts, a restart only starts if it fits
+    pub restart_max_secs: u64,
+    /// Where every seed starts before the random sampling
+    pub init: Init,
+    /// Slots checked exactly for every musician by the greedy init
+    pub greedy_candidates: usize,
+    /// Time budget of the greedy init, the musicians left after it go to their best slots
+    pub greedy_max_secs: u64,
+    /// What improves the best random sample of every seed
+    pub optimizer: Optimizer,
+    /// Stages such as `greedy,anneal:60s,swap,volume`, overrides `init` and `optimizer`
+    pub pipeline: Option<String>,
+    pub annealing: Annealing,
+    pub lns: LargeNeighbourhood,
+    /// How often the best solution so far is checkpointed
+    pub checkpoint_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Init {
+    /// Uniform random placement
+    Random,
+    /// Stage perimeter facing the audience first, see `construct::boundary_first`
+    Boundary,
+    /// One musician at a time on the best slot, see `construct::greedy`
+    Greedy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Optimizer {
+    /// Gradient descent on the smooth score
+    Descent,
+    /// Simulated annealing with shift, swap and teleport moves
+    Anneal,
+}
+
+/// How the temperature goes from `t_start` to `t_end` over the time budget
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Schedule {
+    Linear,
+    Geometric,
+}
+
+/// Simulated annealing parameters. Temperatures are in score units: a move that loses `t`
+/// points is accepted with probability 1/e.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Annealing {
+    pub t_start: f64,
+    pub t_end: f64,
+    pub schedule: Schedule,
+    pub max_iters: u64,
+    pub max_secs: u64,
+    /// Shift moves go up to this far in each coordinate
+    pub shift: f64,
+    /// Seed of the first task, task `i` uses `seed + i`
+    pub seed: u64,
+}
+
+/// Which musicians an LNS iteration removes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Destroy {
+    /// Uniformly random musicians
+    Random,
+    /// A random musician and its nearest neighbours
+    Cluster,
+    /// Random musicians among those contributing the least
+    Worst,
+    /// Any of the above, chosen at random every iteration
+    Mixed,
+}
+
+/// Large neighbourhood search parameters, see `lns::lns`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LargeNeighbourhood {
+    pub max_iters: u64,
+    pub max_secs: u64,
+    /// Musicians removed and reinserted per iteration
+    pub n_removed: usize,
+    pub destroy: Destroy,
+    /// Free slots checked exactly for every reinserted musician
+    pub candidates: usize,
+}
+
+impl Default for LargeNeighbourhood {
+    fn default() -> Self {
+        Self {
+            max_iters: 1_000_000,
+            max_secs: 60,
+            n_removed: 8,
+            destroy: Destroy::Mixed,
+            candidates: 16,
+        }
+    }
+}
+
+impl Default for Annealing {
+    fn default() -> Self {
+        Self {
+            t_start: 1e7,
+            t_end: 1e4,
+            schedule: Schedule::Geometric,
+            max_iters: 1_000_000,
+            max_secs: 60,
+            shift: 10.0,
+            seed: 1,
+        }
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            rand_seed: 1,
+            rand_iters: 1000,
+            rand_max_secs: 1000,
+            descent_iters: 1000,
+            descent_max_secs: 1000,
+            swap_max_secs: 60,
+            shield_max_secs: 60,
+            n_threads: 1,
+            n_seeds: 1,
+            restarts: 0,
+            perturb_musicians: 5,
+            restart_max_secs: 3600,
+            init: Init::Random,
+            greedy_candidates: 32,
+            greedy_max_secs: 60,
+            optimizer: Optimizer::Descent,
+            pipeline: None,
+            annealing: Annealing::default(),
+            lns: LargeNeighbourhood::default(),
+            checkpoint_secs: 60,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -0,0 +1,243 @@
+use std::cmp::Reverse;
+use std::time::Instant;
+
+use float_ord::FloatOrd;
+use memegeom::{
+    geom::distance::pt_pt_dist,
+    primitive::{point::Pt, pt},
+};
+use rayon::prelude::*;
+
+use crate::{
+    assignment::{assign, instrument_slot_values},
+    model::problem::{Problem, Solution},
+    scoring::{ScoreState, ScoringRules, BOUND_MIN_DIST},
+    shadow::ShadowMap,
+    slots::{densest_slots, edge_slots, ensure_capacity, square_slots, Edge},
+    stop,
+};
+
+/// Fills the stage perimeter first, starting with the edge facing the most attendees that like
+/// the problem's instruments, then the interior rows nearest to that edge. Which musician goes
+/// to which of the chosen slots is decided by `assign`.
+pub fn boundary_first(rules: ScoringRules, problem: &Problem) -> anyhow::Result<Solution> {
+    ensure_capacity(problem)?;
+    let mut edges = edge_slots(problem);
+    let mass: Vec<f64> = edges
+        .iter()
+        .map(|(edge, slots)| audience_mass(problem, *edge, slots))
+        .collect();
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by_key(|idx| FloatOrd(-mass[*idx]));
+    log::info!(
+        "boundary_first: edges {:?}",
+        order
+            .iter()
+            .map(|idx| (edges[*idx].0, mass[*idx]))
+            .collect::<Vec<_>>()
+    );
+
+    let n = problem.musicians.len();
+    let mut slots: Vec<Pt> = Vec::with_capacity(n);
+    for idx in &order {
+        slots.append(&mut edges[*idx].1);
+    }
+    if slots.len() < n {
+        let front = edge_distance(problem, edges[order[0]].0);
+        let mut interior: Vec<Pt> = square_slots(problem)
+            .into_iter()
+            .filter(|p| slots.iter().all(|s| pt_pt_dist(p, s) >= BOUND_MIN_DIST))
+            .collect();
+        interior.sort_by_key(|p| FloatOrd(front(p)));
+        slots.extend(interior);
+    }
+    if slots.len() < n {
+        anyhow::bail!(
+            "boundary_first: only {} slots for {n} musicians",
+            slots.len()
+        );
+    }
+    slots.truncate(n);
+    let (solution, score) = assign(rules, problem, &slots)?;
+    log::info!("boundary_first: score={score}");
+    Ok(solution)
+}
+
+/// Places musicians one at a time on the free slot of the densest lattice with the largest exact
+/// score gain given the musicians already placed, blocking and `qi` included.
+///
+/// Musicians go in order of their best value without blocking. Only the `max_candidates` free
+/// slots where the instrument is worth the most without blocking, pillar shadows included if
+/// there are `shadows`, are checked exactly, in parallel: O(M·max_candidates·A·M) in total.
+/// Once `max_secs` pass or a stop is requested, the remaining musicians go straight to their most
+/// valuable free slot without the exact check.
+pub fn greedy(
+    rules: ScoringRules,
+    problem: &Problem,
+    max_candidates: usize,
+    max_secs: u64,
+    shadows: Option<&ShadowMap>,
+) -> anyhow::Result<Solution> {
+    ensure_capacity(problem)?;
+    let slots = densest_slots(problem);
+    let values = instrument_slot_values(problem, &slots, shadows);
+    let best_value = |musician_idx: usize| {
+        values[problem.musicians[musician_idx] as usize]
+            .iter()
+            .fold(f64::NEG_INFINITY, |a, b| a.max(*b))
+    };
+    let mut order: Vec<usize> = (0..problem.musicians.len()).collect();
+    order.sort_by_key(|idx| FloatOrd(-best_value(*idx)));
+
+    let mut state = ScoreState::empty(rules, problem);
+    let mut is_free = vec![true; slots.len()];
+    let start = Instant::now();
+    let mut is_exact = true;
+    for (step, musician_idx) in order.into_iter().enumerate() {
+        let row = &values[problem.musicians[musician_idx] as usize];
+        if is_exact && (start.elapsed().as_secs() > max_secs || stop::requested()) {
+            log::info!("greedy: step={step} out of time or stopped, placing the rest by value");
+            is_exact = false;
+        }
+        if !is_exact {
+            let slot_idx = (0..slots.len())
+                .filter(|s| is_free[*s])
+                .max_by_key(|s| (FloatOrd(row[*s]), Reverse(*s)))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("greedy: no free slot for musician {musician_idx}")
+                })?;
+            state.place(musician_idx, slots[slot_idx]);
+            is_free[slot_idx] = false;
+            continue;
+        }
+        let mut candidates: Vec<usize> = (0..slots.len()).filter(|s| is_free[*s]).collect();
+        candidates.sort_by_key(|s| FloatOrd(-row[*s]));
+        candidates.truncate(max_candidates.max(1));
+        let (gain, slot_idx) = candidates
+            .par_iter()
+            .map(|s| {
+                let gain = state.placement_gain(musician_idx, slots[*s]);
+                (FloatOrd(gain), Reverse(*s))
+            })
+            .max()
+            .map(|(FloatOrd(gain), Reverse(s))| (gain, s))
+            .ok_or_else(|| anyhow::anyhow!("greedy: no free slot for musician {musician_idx}"))?;
+        state.place(musician_idx, slots[slot_idx]);
+        is_free[slot_idx] = false;
+        log::info!(
+            "greedy: step={step} musician={musician_idx} slot={} gain={gain} score={}",
+            slots[slot_idx],
+            state.score()
+        );
+    }
+    log::info!("greedy: score={}", state.score());
+    Ok(state.to_solution())
+}
+
+// Attendees beyond the edge, each weighted by how much it likes the problem's musicians and how
+// close it is to the middle of the edge
+fn audience_mass(problem: &Problem, edge: Edge, slots: &[Pt]) -> f64 {
+    if slots.is_empty() {
+        return 0.0;
+    }
+    let (x0, y0) = (problem.stage_bottom_left[0], problem.stage_bottom_left[1]);
+    let (x1, y1) = (x0 + problem.stage_width, y0 + problem.stage_height);
+    let middle = pt(
+        slots.iter().map(|p| p.x).sum::<f64>() / slots.len() as f64,
+        slots.iter().map(|p| p.y).sum::<f64>() / slots.len() as f64,
+    );
+    problem
+        .attendees
+        .iter()
+        .filter(|a| match edge {
+            Edge::Bottom => a.y < y0,
+            Edge::Top => a.y > y1,
+            Edge::Left => a.x < x0,
+            Edge::Right => a.x > x1,
+        })
+        .map(|a| {
+            let liking: f64 = problem
+                .musicians
+                .iter()
+                .map(|instrument| a.tastes[*instrument as usize].max(0.0))
+                .sum();
+            liking / pt_pt_dist(&pt(a.x, a.y), &middle).powi(2)
+        })
+        .sum()
+}
+
+// Distance from a point to the stage edge
+fn edge_distance(problem: &Problem, edge: Edge) -> impl Fn(&Pt) -> f64 {
+    let (x0, y0) = (problem.stage_bottom_left[0], problem.stage_bottom_left[1]);
+    let (x1, y1) = (x0 + problem.stage_width, y0 + problem.stage_height);
+    move |p: &Pt| match edge {
+        Edge::Bottom => p.y - y0,
+        Edge::Top => y1 - p.y,
+        Edge::Left => p.x - x0,
+        Edge::Right => x1 - p.x,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        construct::{boundary_first, greedy},
+        model::problem::{Attendee, Problem},
+        scoring::{evaluate_exact, is_valid_placement, ScoringRules},
+    };
+
+    fn problem(n_musicians: usize) -> Problem {
+        Problem {
+            room_width: 300.0,
+            room_height: 300.0,
+            stage_width: 60.0,
+            stage_height: 50.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: (0..n_musicians).map(|i| (i % 2) as i32).collect(),
+            attendees: vec![
+                Attendee {
+                    x: 130.0,
+                    y: 250.0,
+                    tastes: vec![1000.0, 500.0],
+                },
+                Attendee {
+                    x: 20.0,
+                    y: 120.0,
+                    tastes: vec![10.0, -500.0],
+                },
+            ],
+            pillars: vec![],
+        }
+    }
+
+    #[test]
+    pub fn fills_the_edge_facing_the_audience() {
+        let prob = problem(5);
+        let solution = boundary_first(ScoringRules::Lightning, &prob).unwrap();
+        assert!(is_valid_placement(&prob, &solution));
+        // the top row fits all five
+        assert!(solution.placements.iter().all(|p| p.y == 140.0));
+    }
+
+    #[test]
+    pub fn interior_is_used_when_perimeter_is_full() {
+        // the 40x30 margin rectangle has 14 perimeter slots and 20 in the square grid
+        let prob = problem(18);
+        let solution = boundary_first(ScoringRules::Full, &prob).unwrap();
+        assert!(is_valid_placement(&prob, &solution));
+        assert!(boundary_first(ScoringRules::Full, &problem(21)).is_err());
+    }
+
+    #[test]
+    pub fn greedy_is_valid_and_not_worse_than_boundary() {
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = problem(12);
+            let solution = greedy(rules, &prob, 100, 60, None).unwrap();
+            assert!(is_valid_placement(&prob, &solution));
+            let boundary = boundary_first(rules, &prob).unwrap();
+            assert!(
+                evaluate_exact(rules, &prob, &solution) >= evaluate_exact(rules, &prob, &boundary)
+            );
+        }
+    }
+}
@@ -1,6 +1,21 @@
+pub mod annealing;
+pub mod assignment;
+pub mod checkpoint;
 pub mod config;
+pub mod construct;
 pub mod geometry;
+pub mod lns;
 pub mod logger;
 pub mod model;
+pub mod progress;
+pub mod random_solution;
+pub mod repair;
 pub mod scoring;
+pub mod shadow;
+pub mod shield;
+pub mod slots;
+pub mod stop;
+pub mod strategy;
+pub mod validation;
+pub mod visibility;
 pub mod visualize;
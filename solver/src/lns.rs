@@ -0,0 +1,250 @@
+use std::time::Instant;
+
+use float_ord::FloatOrd;
+use memegeom::{
+    geom::distance::pt_pt_dist,
+    primitive::{point::Pt, pt},
+};
+use rand::{seq::index, Rng};
+
+use crate::{
+    assignment::instrument_slot_values,
+    config::{Destroy, LargeNeighbourhood},
+    model::problem::{Problem, Solution},
+    progress::Progress,
+    scoring::{ScoreState, ScoringRules, BOUND_MIN_DIST},
+    shadow::ShadowMap,
+    slots::densest_slots,
+    stop,
+    validation::ensure_complete,
+};
+
+/// Large neighbourhood search: removes `n_removed` musicians, puts them back one at a time on
+/// the free slot with the largest exact gain, and keeps the result only if the score improved.
+///
+/// Unlike gradient steps, one iteration can carry a whole group of musicians across the stage.
+/// Candidates for a musician are its old position and the `candidates` free slots of the
+/// densest lattice where its instrument is worth the most without blocking, counting only the
+/// attendees outside the pillar shadows if there are `shadows`.
+#[allow(clippy::too_many_arguments)]
+pub fn lns<R: Rng>(
+    task_id: usize,
+    rules: ScoringRules,
+    prob: &Problem,
+    solution: &Solution,
+    params: &LargeNeighbourhood,
+    rng: &mut R,
+    shadows: Option<&ShadowMap>,
+    progress: Progress,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
+    let n = prob.musicians.len();
+    let slots = densest_slots(prob);
+    let values = instrument_slot_values(prob, &slots, shadows);
+    // slots of every instrument from the most valuable one
+    let slot_order: Vec<Vec<usize>> = values
+        .iter()
+        .map(|row| {
+            let mut order: Vec<usize> = (0..slots.len()).collect();
+            order.sort_by_key(|s| FloatOrd(-row[*s]));
+            order
+        })
+        .collect();
+    let mut state = ScoreState::new(rules, prob, solution);
+    let initial_score = state.score();
+    log::info!(
+        "task={task_id} lns initial score={initial_score} n_removed={} destroy={:?}",
+        params.n_removed,
+        params.destroy
+    );
+    if n == 0 {
+        return Ok(solution.clone());
+    }
+
+    let start = Instant::now();
+    let mut n_improved = 0;
+    let mut iteration = 0;
+    while iteration < params.max_iters && start.elapsed().as_secs() < params.max_secs {
+        if stop::requested() {
+            log::info!("task={task_id} iteration={iteration} lns stop requested");
+            break;
+        }
+        iteration += 1;
+        let destroy = match params.destroy {
+            Destroy::Mixed => {
+                [Destroy::Random, Destroy::Cluster, Destroy::Worst][rng.gen_range(0..3)]
+            }
+            destroy => destroy,
+        };
+        let removed = pick_removed(&state, n, destroy, params.n_removed.min(n), rng);
+        let old: Vec<Pt> = removed.iter().map(|idx| state.position(*idx)).collect();
+        let old_score = state.score();
+        for idx in &removed {
+            state.remove(*idx);
+        }
+
+        let mut is_repaired = true;
+        for (idx, old) in removed.iter().zip(&old) {
+            let is_free = |p: &Pt| {
+                (0..n).all(|other_idx| {
+                    !state.is_placed(other_idx)
+                        || pt_pt_dist(p, &state.position(other_idx)) >= BOUND_MIN_DIST
+                })
+            };
+            let mut candidates: Vec<Pt> = slot_order[prob.musicians[*idx] as usize]
+                .iter()
+                .map(|s| slots[*s])
+                .filter(|p| is_free(p))
+                .take(params.candidates.max(1))
+                .collect();
+            if is_free(old) {
+                candidates.push(*old);
+            }
+            let best = candidates
+                .iter()
+                .map(|p| {
+                    (
+                        FloatOrd(state.placement_gain(*idx, *p)),
+                        FloatOrd(p.x),
+                        FloatOrd(p.y),
+                    )
+                })
+                .max();
+            match best {
+                Some((_, FloatOrd(x), FloatOrd(y))) => state.place(*idx, pt(x, y)),
+                None => {
+                    is_repaired = false;
+                    break;
+                }
+            }
+        }
+
+        if is_repaired && state.score() > old_score {
+            n_improved += 1;
+            log::info!(
+                "task={task_id} iteration={iteration} lns destroy={destroy:?} improved score from {old_score} to {}",
+                state.score()
+            );
+            progress.report(task_id, &state.to_solution(), state.score());
+        } else {
+            for idx in &removed {
+                if state.is_placed(*idx) {
+                    state.remove(*idx);
+                }
+            }
+            for (idx, old) in removed.iter().zip(&old) {
+                state.place(*idx, *old);
+            }
+        }
+        if iteration % 100 == 0 {
+            log::info!(
+                "task={task_id} iteration={iteration} lns score={} improved={n_improved}",
+                state.score()
+            );
+        }
+    }
+    log::info!(
+        "task={task_id} lns improved score from {initial_score} to {} in {iteration} iterations, {n_improved} improving",
+        state.score()
+    );
+    Ok(state.to_solution())
+}
+
+/// The musicians an iteration removes, in the order they are put back.
+fn pick_removed<R: Rng>(
+    state: &ScoreState,
+    n: usize,
+    destroy: Destroy,
+    n_removed: usize,
+    rng: &mut R,
+) -> Vec<usize> {
+    match destroy {
+        Destroy::Random | Destroy::Mixed => index::sample(rng, n, n_removed).into_vec(),
+        Destroy::Cluster => {
+            let center = state.position(rng.gen_range(0..n));
+            let mut order: Vec<usize> = (0..n).collect();
+            order.sort_by_key(|idx| FloatOrd(pt_pt_dist(&center, &state.position(*idx))));
+            order.truncate(n_removed);
+            order
+        }
+        Destroy::Worst => {
+            let mut order: Vec<usize> = (0..n).collect();
+            order.sort_by_key(|idx| FloatOrd(state.musician_score(*idx)));
+            let pool = (2 * n_removed).min(n);
+            index::sample(rng, pool, n_removed)
+                .into_iter()
+                .map(|i| order[i])
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        config::{Destroy, LargeNeighbourhood},
+        lns::lns,
+        model::problem::{Attendee, Position, Problem, Solution},
+        progress::Progress,
+        scoring::{evaluate_exact, is_valid_placement, ScoringRules},
+    };
+
+    #[test]
+    pub fn instruments_move_towards_their_audience() {
+        let prob = Problem {
+            room_width: 300.0,
+            room_height: 300.0,
+            stage_width: 60.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0, 1, 0, 1],
+            attendees: vec![
+                Attendee {
+                    x: 130.0,
+                    y: 200.0,
+                    tastes: vec![1000.0, -10.0],
+                },
+                Attendee {
+                    x: 130.0,
+                    y: 20.0,
+                    tastes: vec![-10.0, 1000.0],
+                },
+            ],
+            pillars: vec![],
+        };
+        // the violins face the attendee that dislikes them
+        let solution = Solution::new(vec![
+            Position::new(110.0, 110.0),
+            Position::new(120.0, 130.0),
+            Position::new(130.0, 110.0),
+            Position::new(140.0, 130.0),
+        ]);
+        let params = LargeNeighbourhood {
+            max_iters: 200,
+            max_secs: 60,
+            n_removed: 2,
+            destroy: Destroy::Mixed,
+            candidates: 8,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let improved = lns(
+                0,
+                rules,
+                &prob,
+                &solution,
+                &params,
+                &mut rng,
+                None,
+                Progress::default(),
+            )
+            .unwrap();
+            assert!(is_valid_placement(&prob, &improved));
+            assert!(
+                evaluate_exact(rules, &prob, &improved) > evaluate_exact(rules, &prob, &solution)
+            );
+        }
+    }
+}
@@ -1,18 +1,27 @@
 extern crate core;
 
-pub mod random_solution;
-
 use clap::{Parser as ClapParser, Subcommand};
 use log::LevelFilter;
+use solver::checkpoint::{Checkpoint, Checkpointer};
+use solver::config::{
+    self, Annealing, Destroy, Init, LargeNeighbourhood, Optimizer, Schedule, Search,
+};
 use solver::logger::configure;
-use solver::model::problem::{Problem, ProblemFile};
-use solver::scoring::bound_penalty;
+use solver::model::problem::{Problem, ProblemFile, Solution};
+use solver::random_solution::get_random_solutions;
+use solver::repair::repair;
+use solver::scoring::{evaluate_exact, is_valid_placement, score_breakdown, ScoringRules};
+use solver::slots::ensure_capacity;
+use solver::stop;
+use solver::strategy::pipeline_spec;
+use solver::validation::{validate, ValidationError};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
-
-use crate::random_solution::get_random_solutions;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use threadpool::ThreadPool;
 
 #[derive(Debug, Clone, ClapParser)]
 #[clap(author, version, about, long_about = None)]
@@ -21,9 +30,13 @@ pub struct Args {
     subcommand: CliCommand,
 }
 
+// parsed once, the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Subcommand)]
 pub enum CliCommand {
     Problem(ProblemArgs),
+    Problems(ProblemsArgs),
+    Score(ScoreArgs),
 }
 
 #[derive(Debug, Clone, clap::Args)]
@@ -34,6 +47,27 @@ pub struct ProblemArgs {
     output: PathBuf,
     #[clap(short, long, value_parser)]
     log: String,
+    /// Also write the per-musician/attendee/instrument score breakdown as JSON
+    #[clap(long, value_parser)]
+    breakdown: Option<PathBuf>,
+    /// Write the solution even if it fails validation
+    #[clap(long)]
+    force: bool,
+    /// Scoring rules, by default derived from the problem id in the file name
+    #[clap(long, value_enum)]
+    rules: Option<ScoringRules>,
+    /// Start every seed from this solution instead of the `--init` placement
+    #[clap(long, value_parser)]
+    init_solution: Option<PathBuf>,
+    /// Periodically write the best solution so far to this checkpoint file
+    #[clap(long, value_parser)]
+    checkpoint: Option<PathBuf>,
+    #[clap(long, value_parser, default_value_t = 60)]
+    checkpoint_secs: u64,
+    /// Continue the run of this checkpoint from its last finished pipeline step, the pipeline
+    /// must be the same
+    #[clap(long, value_parser, conflicts_with = "init_solution")]
+    resume: Option<PathBuf>,
     #[clap(long, value_parser, default_value_t = 1)]
     rand_seed: u64,
     #[clap(long, value_parser, default_value_t = 1000)]
@@ -44,15 +78,97 @@ pub struct ProblemArgs {
     descent_iters: u64,
     #[clap(long, value_parser, default_value_t = 1000)]
     descent_max_secs: u64,
+    /// Time budget of the swap pass after the descent
+    #[clap(long, value_parser, default_value_t = 60)]
+    swap_max_secs: u64,
+    /// Time budget of the `shield` pipeline stage
+    #[clap(long, value_parser, default_value_t = 60)]
+    shield_max_secs: u64,
     #[clap(long, value_parser, default_value_t = 1)]
     n_threads: usize,
     #[clap(long, value_parser, default_value_t = 1)]
     n_seeds: usize,
+    /// Times every seed reruns its pipeline from a perturbed copy of the best solution of all seeds
+    #[clap(long, value_parser, default_value_t = 0)]
+    restarts: u64,
+    /// Musicians moved to random spots before a restart
+    #[clap(long, value_parser, default_value_t = 5)]
+    perturb_musicians: usize,
+    /// Time budget of every seed over all its restarts, a restart only starts if it fits
+    #[clap(long, value_parser, default_value_t = 3600)]
+    restart_max_secs: u64,
+    /// Where every seed starts before the random sampling
+    #[clap(long, value_enum, default_value_t = Init::Random)]
+    init: Init,
+    /// Slots checked exactly for every musician by the greedy init
+    #[clap(long, value_parser, default_value_t = 32)]
+    greedy_candidates: usize,
+    /// Time budget of the greedy init, the musicians left after it go to their best slots
+    #[clap(long, value_parser, default_value_t = 60)]
+    greedy_max_secs: u64,
+    /// What improves the best random sample of every seed
+    #[clap(long, value_enum, default_value_t = Optimizer::Descent)]
+    optimizer: Optimizer,
+    /// Stages such as `greedy,anneal:60s,swap,volume`, overrides `--init` and `--optimizer`
+    #[clap(long, value_parser)]
+    pipeline: Option<String>,
+    /// Initial annealing temperature, in score units
+    #[clap(long, value_parser, default_value_t = 1e7)]
+    anneal_t_start: f64,
+    /// Final annealing temperature, in score units
+    #[clap(long, value_parser, default_value_t = 1e4)]
+    anneal_t_end: f64,
+    #[clap(long, value_enum, default_value_t = Schedule::Geometric)]
+    anneal_schedule: Schedule,
+    #[clap(long, value_parser, default_value_t = 1_000_000)]
+    anneal_iters: u64,
+    #[clap(long, value_parser, default_value_t = 60)]
+    anneal_max_secs: u64,
+    /// Maximal shift of a musician in each coordinate per move
+    #[clap(long, value_parser, default_value_t = 10.0)]
+    anneal_shift: f64,
+    #[clap(long, value_parser, default_value_t = 1)]
+    anneal_seed: u64,
+    #[clap(long, value_parser, default_value_t = 1_000_000)]
+    lns_iters: u64,
+    /// Time budget of the `lns` pipeline stage
+    #[clap(long, value_parser, default_value_t = 60)]
+    lns_max_secs: u64,
+    /// Musicians removed and reinserted per LNS iteration
+    #[clap(long, value_parser, default_value_t = 8)]
+    lns_removed: usize,
+    #[clap(long, value_enum, default_value_t = Destroy::Mixed)]
+    lns_destroy: Destroy,
+    /// Free slots checked exactly for every reinserted musician
+    #[clap(long, value_parser, default_value_t = 16)]
+    lns_candidates: usize,
 }
 
 #[derive(Debug, Clone, clap::Args)]
 pub struct ProblemsArgs {
+    #[clap(short, long, value_parser)]
     config: String,
+    /// Start every problem from the solution with the same file name in this directory, if any
+    #[clap(long, value_parser)]
+    init_from_dir: Option<PathBuf>,
+    /// Continue every problem from its checkpoint in the checkpoints directory, if any, rerunning
+    /// the interrupted pipeline step
+    #[clap(long)]
+    resume: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ScoreArgs {
+    #[clap(short, long, value_parser)]
+    problem: PathBuf,
+    #[clap(short, long, value_parser)]
+    solution: PathBuf,
+    /// Also print the score of every musician
+    #[clap(long)]
+    breakdown: bool,
+    /// Scoring rules, by default derived from the problem id in the file name
+    #[clap(long, value_enum)]
+    rules: Option<ScoringRules>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -60,68 +176,492 @@ fn main() -> anyhow::Result<()> {
 
     match args.subcommand {
         CliCommand::Problem(args) => {
-            let log_config = solver::config::Log {
+            let log_config = config::Log {
                 level: LevelFilter::Info,
-                output: solver::config::LogOutput::File(args.log),
+                output: config::LogOutput::File(args.log.clone()),
             };
             configure(&log_config)?;
-            get_problem_solution(
-                args.input,
-                args.output,
-                args.rand_seed,
-                args.rand_iters,
-                args.rand_max_secs,
-                args.descent_iters,
-                args.descent_max_secs,
-                args.n_threads,
-                args.n_seeds,
-            )
+            stop::install_handlers()?;
+            let search = Search {
+                rand_seed: args.rand_seed,
+                rand_iters: args.rand_iters,
+                rand_max_secs: args.rand_max_secs,
+                descent_iters: args.descent_iters,
+                descent_max_secs: args.descent_max_secs,
+                swap_max_secs: args.swap_max_secs,
+                shield_max_secs: args.shield_max_secs,
+                n_threads: args.n_threads,
+                n_seeds: args.n_seeds,
+                restarts: args.restarts,
+                perturb_musicians: args.perturb_musicians,
+                restart_max_secs: args.restart_max_secs,
+                init: args.init,
+                greedy_candidates: args.greedy_candidates,
+                greedy_max_secs: args.greedy_max_secs,
+                optimizer: args.optimizer,
+                pipeline: args.pipeline.clone(),
+                annealing: Annealing {
+                    t_start: args.anneal_t_start,
+                    t_end: args.anneal_t_end,
+                    schedule: args.anneal_schedule,
+                    max_iters: args.anneal_iters,
+                    max_secs: args.anneal_max_secs,
+                    shift: args.anneal_shift,
+                    seed: args.anneal_seed,
+                },
+                lns: LargeNeighbourhood {
+                    max_iters: args.lns_iters,
+                    max_secs: args.lns_max_secs,
+                    n_removed: args.lns_removed,
+                    destroy: args.lns_destroy,
+                    candidates: args.lns_candidates,
+                },
+                checkpoint_secs: args.checkpoint_secs,
+            };
+            get_problem_solution(&args, &search)
         }
+        CliCommand::Problems(args) => {
+            let config = config::Solver::from_file(&args.config)?;
+            configure(&config.log)?;
+            stop::install_handlers()?;
+            get_problems_solutions(&config, args.init_from_dir.as_deref(), args.resume)
+        }
+        CliCommand::Score(args) => score_solution(args),
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn get_problem_solution(
-    problem_file: PathBuf,
-    solution_file: PathBuf,
-    rand_seed: u64,
-    rand_iters: u64,
-    rand_max_secs: u64,
-    descent_iters: u64,
-    descent_max_secs: u64,
-    n_threads: usize,
-    n_seeds: usize,
+fn score_solution(args: ScoreArgs) -> anyhow::Result<()> {
+    let rules = match args
+        .rules
+        .or_else(|| ScoringRules::from_problem_path(&args.problem))
+    {
+        Some(rules) => rules,
+        None => anyhow::bail!(
+            "can't tell the scoring rules of {:?}, pass --rules",
+            args.problem
+        ),
+    };
+    let problem = read_problem(&args.problem)?.problem;
+    let content = fs::read_to_string(&args.solution)?;
+    let solution: Solution = serde_json::from_str(&content)?;
+
+    let errors = validate(&problem, &solution);
+    if errors.is_empty() {
+        println!("valid");
+    } else {
+        println!("invalid, {} errors:", errors.len());
+        for error in &errors {
+            println!("  {error}");
+        }
+    }
+    let n_musicians = problem.musicians.len();
+    if solution.placements.len() != n_musicians || solution.volumes.len() != n_musicians {
+        anyhow::bail!("can't score a solution that doesn't place every musician");
+    }
+    if args.breakdown {
+        let breakdown = score_breakdown(rules, &problem, &solution);
+        println!("musician instrument volume qi score");
+        for (idx, score) in breakdown.musicians.iter().enumerate() {
+            println!(
+                "{idx} {} {} {} {score}",
+                problem.musicians[idx], solution.volumes[idx], breakdown.qi[idx]
+            );
+        }
+        println!(
+            "blocked pairs: {} by musicians, {} by pillars",
+            breakdown.blocked_by_musician, breakdown.blocked_by_pillar
+        );
+        println!("score ({rules:?}): {}", breakdown.score);
+    } else {
+        let score = evaluate_exact(rules, &problem, &solution);
+        println!("score ({rules:?}): {score}");
+    }
+    Ok(())
+}
+
+fn get_problem_solution(args: &ProblemArgs, search: &Search) -> anyhow::Result<()> {
+    let rules = match args
+        .rules
+        .or_else(|| ScoringRules::from_problem_path(&args.input))
+    {
+        Some(rules) => rules,
+        None => anyhow::bail!(
+            "can't tell the scoring rules of {:?}, pass --rules",
+            args.input
+        ),
+    };
+    let problem_file = read_problem(&args.input)?;
+    let init = match &args.init_solution {
+        Some(path) => Some(read_init_solution(rules, &problem_file.problem, path)?),
+        None => None,
+    };
+    let resume = match &args.resume {
+        Some(path) => Some(read_checkpoint(rules, &problem_file, path)?),
+        None => None,
+    };
+    let pool = ThreadPool::new(search.n_threads);
+    let (solution, score, errors) = solve(
+        rules,
+        &problem_file,
+        search,
+        &pool,
+        init.as_ref(),
+        resume.as_ref(),
+        args.checkpoint.as_deref(),
+    )?;
+    if !errors.is_empty() && !args.force {
+        anyhow::bail!(
+            "solution for {:?} has {} validation errors, not writing it",
+            problem_file.name,
+            errors.len()
+        );
+    }
+    write_solution(&args.output, &solution)?;
+    if let Some(reason) = stop::reason() {
+        log::warn!(
+            "stopped by {reason}, wrote the best solution so far to {:?} with score {score}",
+            args.output
+        );
+    }
+    if let Some(breakdown_file) = &args.breakdown {
+        let breakdown = score_breakdown(rules, &problem_file.problem, &solution);
+        let content = serde_json::to_string_pretty(&breakdown)?;
+        let mut file = File::create(breakdown_file)?;
+        file.write_all(content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// One row of the `problems` summary table
+struct ProblemReport {
+    name: String,
+    rules: Option<ScoringRules>,
+    old_score: Option<f64>,
+    new_score: Option<f64>,
+    status: String,
+}
+
+/// Solves every problem of the config's problem directory. Problems are solved concurrently,
+/// all seeds of all problems share one pool of `search.n_threads` threads. A solution is only
+/// written if it is valid and beats the valid solution already in the solutions directory.
+fn get_problems_solutions(
+    config: &config::Solver,
+    init_from_dir: Option<&Path>,
+    resume: bool,
 ) -> anyhow::Result<()> {
-    let file_name = problem_file
+    let mut problem_paths: Vec<PathBuf> = fs::read_dir(&config.problems.dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    problem_paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    problem_paths.sort_by_key(|path| {
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u32>().ok());
+        (id.is_none(), id, path.clone())
+    });
+    fs::create_dir_all(&config.solutions.dir)?;
+    match &config.checkpoints {
+        Some(checkpoints) => fs::create_dir_all(&checkpoints.dir)?,
+        None if resume => {
+            anyhow::bail!("can't resume without a checkpoints directory in the config")
+        }
+        None => {}
+    }
+    log::info!(
+        "solving {} problems from {:?}",
+        problem_paths.len(),
+        config.problems.dir
+    );
+
+    let pool = ThreadPool::new(config.search.n_threads);
+    let reports: Vec<ProblemReport> = std::thread::scope(|scope| {
+        let handles: Vec<_> = problem_paths
+            .iter()
+            .map(|path| {
+                let pool = pool.clone();
+                scope.spawn(move || solve_problem_file(config, path, &pool, init_from_dir, resume))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("problem solver thread panicked"))
+            .collect()
+    });
+
+    println!(
+        "{:<12} {:<10} {:>16} {:>16} status",
+        "problem", "rules", "old score", "new score"
+    );
+    let format_score = |score: Option<f64>| score.map_or("-".to_string(), |s| format!("{s:.0}"));
+    for report in &reports {
+        println!(
+            "{:<12} {:<10} {:>16} {:>16} {}",
+            report.name,
+            report.rules.map_or("-".to_string(), |r| format!("{r:?}")),
+            format_score(report.old_score),
+            format_score(report.new_score),
+            report.status
+        );
+    }
+    let total: f64 = reports
+        .iter()
+        .map(|r| match (r.old_score, r.new_score) {
+            (Some(old), Some(new)) => old.max(new),
+            (old, new) => old.or(new).unwrap_or(0.0),
+        })
+        .sum();
+    println!("total best score: {total:.0}");
+    if let Some(reason) = stop::reason() {
+        log::warn!("stopped by {reason}, wrote the best solutions found so far");
+        println!("stopped early by {reason}");
+    }
+    Ok(())
+}
+
+fn solve_problem_file(
+    config: &config::Solver,
+    path: &Path,
+    pool: &ThreadPool,
+    init_from_dir: Option<&Path>,
+    resume: bool,
+) -> ProblemReport {
+    let mut report = ProblemReport {
+        name: path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+        rules: config
+            .rules
+            .or_else(|| ScoringRules::from_problem_path(path)),
+        old_score: None,
+        new_score: None,
+        status: String::new(),
+    };
+    let solution_path = config.solutions.dir.join(&report.name);
+    let checkpoint_path = config
+        .checkpoints
+        .as_ref()
+        .map(|checkpoints| checkpoints.dir.join(&report.name));
+    let result = report
+        .rules
+        .ok_or_else(|| anyhow::anyhow!("can't tell the scoring rules, set `rules` in the config"))
+        .and_then(|rules| {
+            let problem_file = read_problem(path)?;
+            report.old_score = existing_score(rules, &problem_file.problem, &solution_path);
+            let init_path = init_from_dir
+                .map(|dir| dir.join(&report.name))
+                .filter(|path| path.exists());
+            let init = match init_path {
+                Some(path) => Some(read_init_solution(rules, &problem_file.problem, &path)?),
+                None => None,
+            };
+            let resume_path = checkpoint_path
+                .as_ref()
+                .filter(|path| resume && path.exists());
+            let resume = match resume_path {
+                Some(path) => Some(read_checkpoint(rules, &problem_file, path)?),
+                None => None,
+            };
+            let (solution, score, errors) = solve(
+                rules,
+                &problem_file,
+                &config.search,
+                pool,
+                init.as_ref(),
+                resume.as_ref(),
+                checkpoint_path.as_deref(),
+            )?;
+            report.new_score = Some(score);
+            if !errors.is_empty() {
+                return Ok(format!("invalid, {} errors", errors.len()));
+            }
+            if report.old_score.is_some_and(|old| old >= score) {
+                return Ok("kept".to_string());
+            }
+            write_solution(&solution_path, &solution)?;
+            Ok("written".to_string())
+        });
+    report.status = result.unwrap_or_else(|error| {
+        log::error!("failed to solve {path:?}: {error}");
+        format!("error: {error}")
+    });
+    report
+}
+
+/// Score of the solution already written for the problem, `None` if there is no valid one
+fn existing_score(rules: ScoringRules, problem: &Problem, solution_path: &Path) -> Option<f64> {
+    let content = fs::read_to_string(solution_path).ok()?;
+    let solution: Solution = match serde_json::from_str(&content) {
+        Ok(solution) => solution,
+        Err(error) => {
+            log::warn!("can't parse {solution_path:?}: {error}");
+            return None;
+        }
+    };
+    if !validate(problem, &solution).is_empty() {
+        log::warn!("existing solution {solution_path:?} is invalid");
+        return None;
+    }
+    Some(evaluate_exact(rules, problem, &solution))
+}
+
+/// A solution to start from, repaired if it is invalid
+fn read_init_solution(
+    rules: ScoringRules,
+    problem: &Problem,
+    path: &Path,
+) -> anyhow::Result<Solution> {
+    let content = fs::read_to_string(path)?;
+    let solution: Solution = serde_json::from_str(&content)?;
+    checked_init(rules, problem, solution, path)
+}
+
+/// A checkpoint of the problem to resume from, its solution repaired if it is invalid
+fn read_checkpoint(
+    rules: ScoringRules,
+    problem_file: &ProblemFile,
+    path: &Path,
+) -> anyhow::Result<Checkpoint> {
+    let checkpoint = Checkpoint::read(path)?;
+    let name = problem_file.name.to_string_lossy();
+    if checkpoint.problem != name {
+        anyhow::bail!(
+            "checkpoint {path:?} is for problem {}, not {name}",
+            checkpoint.problem
+        );
+    }
+    log::info!(
+        "resuming {path:?} at step {} of {}",
+        checkpoint.steps,
+        checkpoint.pipeline
+    );
+    let solution = checked_init(rules, &problem_file.problem, checkpoint.solution, path)?;
+    Ok(Checkpoint {
+        solution,
+        ..checkpoint
+    })
+}
+
+fn checked_init(
+    rules: ScoringRules,
+    problem: &Problem,
+    solution: Solution,
+    path: &Path,
+) -> anyhow::Result<Solution> {
+    let errors = validate(problem, &solution);
+    if errors.is_empty() {
+        log::info!(
+            "starting from {path:?} with score {}",
+            evaluate_exact(rules, problem, &solution)
+        );
+        return Ok(solution);
+    }
+    for error in &errors {
+        log::warn!("invalid initial solution {path:?}: {error}");
+    }
+    let repaired = repair(rules, problem, &solution)?;
+    log::info!(
+        "starting from repaired {path:?} with score {}",
+        evaluate_exact(rules, problem, &repaired)
+    );
+    Ok(repaired)
+}
+
+fn read_problem(path: &Path) -> anyhow::Result<ProblemFile> {
+    let file_name = path
         .file_name()
         .expect("Should have been read file name")
         .to_os_string();
-    let content = fs::read_to_string(problem_file).expect("Should have been able to read the file");
+    let content = fs::read_to_string(path)?;
     let problem: Problem = serde_json::from_str(&content)?;
-    let problem_file = ProblemFile::new(file_name, problem);
+    Ok(ProblemFile::new(file_name, problem))
+}
 
+fn write_solution(path: &Path, solution: &Solution) -> anyhow::Result<()> {
+    let content = serde_json::to_string(solution)?;
+    let mut file = File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Runs the search, repairs the result if needed and validates it. A resumed run continues with
+/// the checkpoint's seed from its solution and step, rerunning the interrupted step in full.
+fn solve(
+    rules: ScoringRules,
+    problem_file: &ProblemFile,
+    search: &Search,
+    pool: &ThreadPool,
+    init: Option<&Solution>,
+    resume: Option<&Checkpoint>,
+    checkpoint_path: Option<&Path>,
+) -> anyhow::Result<(Solution, f64, Vec<ValidationError>)> {
+    if let Err(error) = ensure_capacity(&problem_file.problem) {
+        log::warn!(
+            "{:?}: {error}, only stages that don't need the lattice can place everyone",
+            problem_file.name
+        );
+    }
     log::info!(
-        "solving {:?} n_musicians={} n_attendees={}",
+        "solving {:?} n_musicians={} n_attendees={} rules={rules:?}",
         problem_file.name,
         problem_file.problem.musicians.len(),
         problem_file.problem.attendees.len()
     );
+    let spec = pipeline_spec(search);
+    let (search, init, first_step) = match resume {
+        Some(checkpoint) if checkpoint.pipeline != spec => anyhow::bail!(
+            "checkpoint of {:?} is for pipeline {}, not {spec}",
+            problem_file.name,
+            checkpoint.pipeline
+        ),
+        Some(checkpoint) => (
+            Search {
+                rand_seed: checkpoint.rand_seed,
+                ..search.clone()
+            },
+            Some(&checkpoint.solution),
+            checkpoint.steps,
+        ),
+        None => (search.clone(), init, 0),
+    };
+    let checkpoint = checkpoint_path.map(|path| {
+        Arc::new(Checkpointer::new(
+            path.to_path_buf(),
+            Duration::from_secs(search.checkpoint_secs),
+            problem_file.name.to_string_lossy().into_owned(),
+            spec,
+            search.rand_seed,
+            search.n_seeds,
+        ))
+    });
     let (solution, score) = get_random_solutions(
+        rules,
         &problem_file.problem,
-        rand_seed,
-        rand_iters,
-        rand_max_secs,
-        descent_iters,
-        descent_max_secs,
-        n_threads,
-        n_seeds,
-    );
+        &search,
+        pool,
+        init,
+        first_step,
+        checkpoint,
+    )?;
     log::info!("score for {:?}: {score}", problem_file.name);
-    log::info!("correctness {:?}", problem_file.name);
-    let penalty = bound_penalty(&problem_file.problem, &solution);
-    log::info!("penalty for {:?}: {penalty}", problem_file.name);
-    let content = serde_json::to_string(&solution)?;
-    let mut file = File::create(solution_file)?;
-    file.write_all(content.as_bytes())?;
-    Ok(())
+    if let Some(reason) = stop::reason() {
+        log::warn!(
+            "search for {:?} stopped early by {reason}",
+            problem_file.name
+        );
+    }
+    let (solution, score) = if is_valid_placement(&problem_file.problem, &solution) {
+        (solution, score)
+    } else {
+        log::warn!("solution for {:?} is invalid, repairing", problem_file.name);
+        let repaired = repair(rules, &problem_file.problem, &solution)?;
+        let score = evaluate_exact(rules, &problem_file.problem, &repaired);
+        log::info!("repaired score for {:?}: {score}", problem_file.name);
+        (repaired, score)
+    };
+    let errors = validate(&problem_file.problem, &solution);
+    for error in &errors {
+        log::error!("invalid solution for {:?}: {error}", problem_file.name);
+    }
+    Ok((solution, score, errors))
 }
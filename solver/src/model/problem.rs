@@ -39,7 +39,7 @@ pub struct Pillar {
     pub radius: f64,
 }
 
-#[derive(serde::Serialize, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct Solution {
     pub placements: Vec<Position>,
     pub volumes: Vec<f64>,
@@ -52,7 +52,7 @@ impl Solution {
     }
 }
 
-#[derive(serde::Serialize, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+
+use crate::{checkpoint::Checkpointer, model::problem::Solution};
+
+/// The best solution of all tasks of a run, shared while they run so that a task can restart
+/// from what the others have found.
+#[derive(Default)]
+pub struct Incumbent {
+    best: Mutex<Option<(Solution, f64)>>,
+}
+
+impl Incumbent {
+    /// Replaces the incumbent if `score` beats it, returns whether it did.
+    pub fn publish(&self, task_id: usize, solution: &Solution, score: f64) -> bool {
+        let mut best = self.best.lock().expect("incumbent lock is never poisoned");
+        if best.as_ref().is_some_and(|(_, best)| *best >= score) {
+            return false;
+        }
+        log::info!("task={task_id} published incumbent score={score}");
+        *best = Some((solution.clone(), score));
+        true
+    }
+
+    pub fn get(&self) -> Option<(Solution, f64)> {
+        self.best
+            .lock()
+            .expect("incumbent lock is never poisoned")
+            .clone()
+    }
+}
+
+/// Where optimizers report their best solutions as they go: the shared incumbent and the
+/// checkpoint of the run, if any.
+#[derive(Clone, Copy, Default)]
+pub struct Progress<'a> {
+    pub incumbent: Option<&'a Incumbent>,
+    pub checkpoint: Option<&'a Checkpointer>,
+}
+
+impl Progress<'_> {
+    /// The task has `solution` as its best in its current pipeline step.
+    pub fn report(&self, task_id: usize, solution: &Solution, score: f64) {
+        if let Some(incumbent) = self.incumbent {
+            incumbent.publish(task_id, solution, score);
+        }
+        if let Some(checkpoint) = self.checkpoint {
+            checkpoint.progress(task_id, solution, score);
+        }
+    }
+
+    /// The task has finished `steps` pipeline steps with `solution` as its best.
+    pub fn step_done(&self, task_id: usize, steps: usize, solution: &Solution, score: f64) {
+        if let Some(incumbent) = self.incumbent {
+            incumbent.publish(task_id, solution, score);
+        }
+        if let Some(checkpoint) = self.checkpoint {
+            checkpoint.step_done(task_id, steps, solution, score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model::problem::{Position, Solution},
+        progress::Incumbent,
+    };
+
+    #[test]
+    pub fn only_better_solutions_are_published() {
+        let incumbent = Incumbent::default();
+        assert!(incumbent.get().is_none());
+        let first = Solution::new(vec![Position::new(10.0, 10.0)]);
+        let second = Solution::new(vec![Position::new(20.0, 10.0)]);
+        assert!(incumbent.publish(0, &first, 100.0));
+        assert!(!incumbent.publish(1, &second, 100.0));
+        assert!(!incumbent.publish(1, &second, 50.0));
+        assert_eq!(incumbent.get().unwrap().0.placements[0].x, 10.0);
+        assert!(incumbent.publish(1, &second, 150.0));
+        let (best, score) = incumbent.get().unwrap();
+        assert_eq!((best.placements[0].x, score), (20.0, 150.0));
+    }
+}
@@ -1,102 +1,219 @@
-use std::sync::mpsc::channel;
+use std::sync::{mpsc::channel, Arc};
 use std::time::Instant;
 use threadpool::ThreadPool;
 
 use memegeom::{
     geom::distance::pt_pt_dist,
-    primitive::{point::Pt, pt, rt, seg},
+    primitive::{point::Pt, pt, rt},
 };
-use rand::{distributions::Uniform, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
-use solver::{
+use rand::{
+    distributions::Uniform,
+    prelude::Distribution,
+    rngs::StdRng,
+    seq::{index, SliceRandom},
+    Rng, SeedableRng,
+};
+
+use crate::{
+    assignment::assign,
+    checkpoint::Checkpointer,
+    config::Search,
     model::problem::{Position, Problem, Solution},
+    progress::{Incumbent, Progress},
     scoring::{
-        bound_penalty, evaluate_exact, grad, is_att_mus_audible, is_valid_placement, pos_to_pt,
-        pt_to_pos, IMPACT_SCALING_COEF,
+        bound_penalty_gradient, evaluate_exact, is_valid_placement, pos_to_pt, pt_to_pos,
+        ScoreState, ScoringRules, BOUND_MIN_DIST,
     },
+    shadow::{shadow_map, ShadowMap},
+    slots::{densest_slots, ensure_capacity, margin_rect},
+    stop,
+    strategy::{build_pipeline, pipeline_spec, Context, Pipeline},
+    validation::{ensure_complete, MAX_VOLUME},
 };
 
 pub const MUSICIAN_SIZE: f64 = 10.0;
+// Volume intervals narrower than this are not bisected further, see `best_volume`
+const MIN_VOLUME_STEP: f64 = 1e-9;
 
-#[allow(clippy::too_many_arguments)]
+/// Runs the pipeline of `search` in `n_seeds` tasks on the pool. The tasks share the best
+/// solution found so far, see `run_task`.
 pub fn get_random_solutions(
+    rules: ScoringRules,
     problem: &Problem,
-    seed: u64,
-    n_iters: u64,
-    max_secs: u64,
-    descent_iters: u64,
-    descent_max_secs: u64,
-    n_threads: usize,
-    n_seeds: usize,
-) -> (Solution, f64) {
-    let pool = ThreadPool::new(n_threads);
-
+    search: &Search,
+    pool: &ThreadPool,
+    init: Option<&Solution>,
+    first_step: usize,
+    checkpoint: Option<Arc<Checkpointer>>,
+) -> anyhow::Result<(Solution, f64)> {
     struct Message {
         pub solution: Solution,
         pub score: f64,
     }
 
+    let spec = pipeline_spec(search);
+    let pipeline = Arc::new(build_pipeline(&spec, search)?);
+    log::info!("pipeline {}", pipeline.describe());
+    let incumbent = Arc::new(Incumbent::default());
+    let shadows = shadow_map(rules, problem).map(Arc::new);
+
     let (tx, rx) = channel::<Message>();
-    for task_id in 0..n_seeds {
+    for task_id in 0..search.n_seeds {
         let problem = problem.clone();
+        let pipeline = pipeline.clone();
+        let init = init.cloned();
+        let seed = search.rand_seed + task_id as u64;
+        let (restarts, n_perturbed, restart_max_secs) = (
+            search.restarts,
+            search.perturb_musicians,
+            search.restart_max_secs,
+        );
+        let incumbent = incumbent.clone();
+        let shadows = shadows.clone();
+        let checkpoint = checkpoint.clone();
         let tx = tx.clone();
         pool.execute(move || {
-            let seed = seed + (task_id as u64);
-            let mut rng = StdRng::seed_from_u64(seed);
-            let mut best = random_iteration(&mut rng, &problem);
-            let mut best_score = evaluate_exact(&problem, &best);
-            log::info!("task={task_id} initial best_score={best_score} seed={seed} n_iters={n_iters}");
-            let start = Instant::now();
-            for i in 1..=n_iters {
-                let next = random_iteration(&mut rng, &problem);
-                let next_score = evaluate_exact(&problem, &next);
-                let mut is_better = false;
-                if next_score > best_score {
-                    best = next;
-                    best_score = next_score;
-                    is_better = true;
-                }
-                if is_better || i % 10000 == 0 {
-                    log::info!("task={task_id} iteration={i} best_score={best_score}");
-                }
-                if start.elapsed().as_secs() > max_secs {
-                    log::info!("task={task_id} iteration={i} best_score={best_score}. Stopping due to max time");
-                    break;
-                }
-            }
-            let improved = improve_solution(
+            let mut ctx = Context {
                 task_id,
-                &problem,
-                &best,
-                1.0,
-                descent_iters,
-                descent_max_secs,
+                rules,
+                problem: &problem,
+                rng: StdRng::seed_from_u64(seed),
+                shadows: shadows.as_deref(),
+                progress: Progress {
+                    incumbent: Some(incumbent.as_ref()),
+                    checkpoint: checkpoint.as_deref(),
+                },
+            };
+            let result = run_task(
+                &mut ctx,
+                &pipeline,
+                init.as_ref(),
+                first_step,
+                restarts,
+                n_perturbed,
+                restart_max_secs,
             );
-            let updated_volume = update_volume(&problem, &improved);
-            let updated_score = evaluate_exact(&problem, &updated_volume);
-            tx.send(Message {
-                solution: updated_volume,
-                score: updated_score,
-            }).expect("channel will be there waiting for the pool");
+            match result {
+                Ok((solution, score)) => tx
+                    .send(Message { solution, score })
+                    .expect("channel will be there waiting for the pool"),
+                Err(error) => log::error!("task={task_id} failed: {error}"),
+            }
         });
     }
     drop(tx);
 
-    let mut best = Solution::new(vec![]);
-    let mut best_score = 0.0;
-    let mut first_sol = true;
+    let mut best: Option<(Solution, f64)> = None;
     while let Ok(message) = rx.recv() {
-        if first_sol || message.score > best_score {
-            best = message.solution;
-            best_score = message.score;
-            first_sol = false;
+        if best
+            .as_ref()
+            .is_none_or(|(_, score)| message.score > *score)
+        {
+            best = Some((message.solution, message.score));
         }
     }
+    let (best, best_score) =
+        best.ok_or_else(|| anyhow::anyhow!("all {} tasks failed", search.n_seeds))?;
     log::info!("best solution best_score={best_score}");
+    if let Some(checkpoint) = checkpoint {
+        // the solution is still returned, as a periodic write failing only warns too
+        if let Err(error) = checkpoint.write() {
+            log::warn!("can't write the final checkpoint: {error}");
+        }
+    }
 
-    (best, best_score)
+    Ok((best, best_score))
 }
 
-fn random_iteration<R: Rng>(rng: &mut R, problem: &Problem) -> Solution {
+/// Runs the pipeline, then `restarts` more times from the incumbent with `n_perturbed` musicians
+/// moved, so that a task whose pipeline has converged helps the others instead of stopping.
+/// Returns the best solution of all rounds and its score.
+///
+/// Every round reruns the time-limited stages in full, so a round only starts if it fits in
+/// `max_secs` from the start of the task, judging by the longest round so far.
+pub fn run_task(
+    ctx: &mut Context,
+    pipeline: &Pipeline,
+    init: Option<&Solution>,
+    first_step: usize,
+    restarts: u64,
+    n_perturbed: usize,
+    max_secs: u64,
+) -> anyhow::Result<(Solution, f64)> {
+    let task_id = ctx.task_id;
+    let start_time = Instant::now();
+    let mut best = pipeline.run(ctx, init, first_step)?;
+    let mut longest_round = start_time.elapsed();
+    for round in 1..=restarts {
+        if stop::requested() {
+            log::info!("task={task_id} round={round} stop requested, not restarting");
+            break;
+        }
+        if (start_time.elapsed() + longest_round).as_secs_f64() > max_secs as f64 {
+            log::info!(
+                "task={task_id} round={round} another round of {longest_round:?} would exceed {max_secs}s, not restarting"
+            );
+            break;
+        }
+        let round_start = Instant::now();
+        let (start, start_score) = match ctx.progress.incumbent.and_then(Incumbent::get) {
+            Some(incumbent) => incumbent,
+            None => best.clone(),
+        };
+        log::info!(
+            "task={task_id} round={round} restarting from score={start_score}, own best_score={}",
+            best.1
+        );
+        let start = perturb(&mut ctx.rng, ctx.problem, &start, n_perturbed);
+        let next = pipeline.run(ctx, Some(&start), 0)?;
+        longest_round = longest_round.max(round_start.elapsed());
+        if next.1 > best.1 {
+            best = next;
+        }
+    }
+    Ok(best)
+}
+
+/// `solution` with `n_moved` random musicians moved to random free spots of the stage. A musician
+/// stays where it is if no free spot is found.
+pub fn perturb<R: Rng>(
+    rng: &mut R,
+    problem: &Problem,
+    solution: &Solution,
+    n_moved: usize,
+) -> Solution {
+    let Some((left, bottom, right, top)) = margin_rect(problem) else {
+        return solution.clone();
+    };
+    let mut placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let n = placements.len();
+    for idx in index::sample(rng, n, n_moved.min(n)) {
+        for _ in 0..1000 {
+            let p = pt(rng.gen_range(left..=right), rng.gen_range(bottom..=top));
+            let is_free = placements.iter().enumerate().all(|(other_idx, other)| {
+                other_idx == idx || pt_pt_dist(&p, other) >= BOUND_MIN_DIST
+            });
+            if is_free {
+                placements[idx] = p;
+                break;
+            }
+        }
+    }
+    Solution {
+        placements: placements.iter().map(pt_to_pos).collect(),
+        volumes: solution.volumes.clone(),
+    }
+}
+
+/// A uniformly random valid placement, or random lattice slots if sampling keeps colliding.
+///
+/// With `shadows`, every musician is drawn near the points where its instrument is worth the
+/// most counting only the attendees outside the pillar shadows, see `ShadowMap::sample`.
+pub fn random_iteration<R: Rng>(
+    rng: &mut R,
+    problem: &Problem,
+    shadows: Option<&ShadowMap>,
+) -> anyhow::Result<Solution> {
     let x_dist = if problem.stage_width > 2.0 * MUSICIAN_SIZE {
         Some(Uniform::new(
             problem.stage_bottom_left[0] + MUSICIAN_SIZE,
@@ -118,19 +235,24 @@ fn random_iteration<R: Rng>(rng: &mut R, problem: &Problem) -> Solution {
     while positions.len() < problem.musicians.len() {
         iters += 1;
         if iters > problem.musicians.len() * 1000 {
-            panic!("Unable to get random placement");
+            log::info!("Unable to get random placement, picking random slots");
+            return random_slots(rng, problem);
         }
-        let x = if let Some(x_dist) = &x_dist {
-            x_dist.sample(rng)
-        } else {
-            problem.stage_bottom_left[0] + MUSICIAN_SIZE
-        };
-        let y = if let Some(y_dist) = &y_dist {
-            y_dist.sample(rng)
+        let pos = if let Some(shadows) = shadows {
+            shadows.sample(rng, problem.musicians[positions.len()] as usize)
         } else {
-            problem.stage_bottom_left[1] + MUSICIAN_SIZE
+            let x = if let Some(x_dist) = &x_dist {
+                x_dist.sample(rng)
+            } else {
+                problem.stage_bottom_left[0] + MUSICIAN_SIZE
+            };
+            let y = if let Some(y_dist) = &y_dist {
+                y_dist.sample(rng)
+            } else {
+                problem.stage_bottom_left[1] + MUSICIAN_SIZE
+            };
+            pt(x, y)
         };
-        let pos = pt(x, y);
         let is_colliding = positions
             .iter()
             .any(|other| pt_pt_dist(&pos, other) < MUSICIAN_SIZE);
@@ -142,18 +264,39 @@ fn random_iteration<R: Rng>(rng: &mut R, problem: &Problem) -> Solution {
         .into_iter()
         .map(|p| Position::new(p.x, p.y))
         .collect();
-    Solution::new(placements)
+    Ok(Solution::new(placements))
+}
+
+/// Random musicians on random slots of the densest lattice, for stages too crowded for sampling.
+fn random_slots<R: Rng>(rng: &mut R, problem: &Problem) -> anyhow::Result<Solution> {
+    ensure_capacity(problem)?;
+    let placements = densest_slots(problem)
+        .choose_multiple(rng, problem.musicians.len())
+        .map(pt_to_pos)
+        .collect();
+    Ok(Solution::new(placements))
 }
 
+/// Moves every musician a step of `gamma` along the score gradient while that moves them at all.
+///
+/// The gradient can't see pillars, so with `shadows` a step into a shadow that hides more than
+/// it reveals to the musician's instrument, see `ShadowMap::shadow_loss`, is only kept if the
+/// exact score doesn't drop.
+#[allow(clippy::too_many_arguments)]
 pub fn improve_solution(
     task_id: usize,
+    rules: ScoringRules,
     prob: &Problem,
     solution: &Solution,
     gamma: f64,
     n_iters: u64,
     max_secs: u64,
-) -> Solution {
+    shadows: Option<&ShadowMap>,
+    progress: Progress,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
     let mut sol = (*solution).clone();
+    let mut state = ScoreState::new(rules, prob, &sol);
     let start = Instant::now();
     let stage = rt(
         prob.stage_bottom_left[0] + MUSICIAN_SIZE,
@@ -166,6 +309,10 @@ pub fn improve_solution(
             log::info!("task={task_id} iter={it} time limit reached");
             break;
         }
+        if stop::requested() {
+            log::info!("task={task_id} iter={it} stop requested");
+            break;
+        }
         let mut iter_dist = 0.0;
         for mus_idx in 0..prob.musicians.len() {
             log::info!("Improving musician {}", mus_idx);
@@ -173,28 +320,26 @@ pub fn improve_solution(
                 log::info!("task={task_id} iter={it} musician={mus_idx} time limit reached");
                 break;
             }
+            if stop::requested() {
+                log::info!("task={task_id} iter={it} musician={mus_idx} stop requested");
+                break;
+            }
             // let f = |p: &_| {
             //     let mut s = sol.clone();
             //     s.placements[mus_idx] = pt_to_pos(p);
             //     evaluate_exact_full(full, prob, &s) + bound_penalty(prob, &s)
             // };
+            let score_grad = state.gradient(mus_idx);
+            let penalty_grad = bound_penalty_gradient(prob, &sol, mus_idx);
+            let d = pt(score_grad.x - penalty_grad.x, score_grad.y - penalty_grad.y);
             let mut pt = pos_to_pt(&sol.placements[mus_idx]);
             let old_pt = pt;
-            let d = grad(
-                0.1,
-                |p| {
-                    sol.placements[mus_idx] = pt_to_pos(p);
-                    let r = evaluate_exact(prob, &sol) - bound_penalty(prob, &sol);
-                    sol.placements[mus_idx] = pt_to_pos(&old_pt);
-                    r
-                },
-                &pt,
-            );
             let mag = d.mag();
             if mag > 1e-8 {
                 pt += gamma / mag * d;
                 pt = pt.clamp(&stage); // Ensure that the musician does not move out of the stage (but can glide across the boundary)
                 sol.placements[mus_idx] = pt_to_pos(&pt);
+                let instrument = prob.musicians[mus_idx] as usize;
                 if !is_valid_placement(prob, &sol) {
                     // Ensure that the musician does not collide with other musicians
                     log::info!(
@@ -203,47 +348,283 @@ pub fn improve_solution(
                     pt = old_pt;
                     sol.placements[mus_idx] = pt_to_pos(&pt);
                 } else {
-                    iter_dist += pt_pt_dist(&old_pt, &pt);
+                    let old_score = state.score();
+                    state.move_musician(mus_idx, pt);
+                    let is_shadowed = shadows.is_some_and(|shadows| {
+                        shadows.shadow_loss(prob, instrument, &old_pt, &pt) > 0.0
+                    });
+                    if is_shadowed && state.score() < old_score {
+                        log::info!(
+                            "task={task_id} iter={it}, musician={mus_idx} pt={pt} grad={d} would step into a pillar shadow, not moving"
+                        );
+                        state.move_musician(mus_idx, old_pt);
+                        pt = old_pt;
+                        sol.placements[mus_idx] = pt_to_pos(&pt);
+                    } else {
+                        iter_dist += pt_pt_dist(&old_pt, &pt);
+                    }
                 }
             }
-            let score = evaluate_exact(prob, &sol);
+            let score = state.score();
             log::info!(
                 "task={task_id} iter={it}, musician={mus_idx} pt={pt} grad={d}, score={score}"
             );
         }
+        progress.report(task_id, &sol, state.score());
         if iter_dist < 1e-3 {
             log::info!("task={task_id} iter={it} iter_dist={iter_dist} is too low, stopping");
             break;
         }
     }
-    sol
+    Ok(sol)
 }
 
-pub fn update_volume(p: &Problem, s: &Solution) -> Solution {
-    let mut res = s.clone();
-    // let score0 = evaluate_exact(p, &res);
-    // log::info!("Updating volumes. Initial score: {}", score0);
-    for musician_idx in 0..p.musicians.len() {
-        let total = p.attendees.iter().fold(0.0, |sum, att| {
-            let taste = att.tastes[p.musicians[musician_idx] as usize];
-            let a = pt(att.x, att.y);
-            let m = pt(
-                res.placements[musician_idx].x,
-                res.placements[musician_idx].y,
+/// Optimally reassigns the musicians to their current positions ignoring blocking, keeps the
+/// result if it also scores better exactly.
+pub fn reassign(
+    task_id: usize,
+    rules: ScoringRules,
+    prob: &Problem,
+    solution: &Solution,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
+    let slots: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let score = evaluate_exact(rules, prob, solution);
+    let reassigned = match assign(rules, prob, &slots) {
+        Ok((assigned, assigned_score)) if assigned_score > score => {
+            log::info!(
+                "task={task_id} reassignment improved score from {score} to {assigned_score}"
             );
-            let att_mus_seg = seg(a, m);
-            let distance = pt_pt_dist(&a, &m);
-            let is_audible = is_att_mus_audible(p, s, musician_idx, &att_mus_seg);
-            sum + if !is_audible {
-                0.0
-            } else {
-                (IMPACT_SCALING_COEF * taste / distance.powi(2)).ceil()
+            assigned
+        }
+        Ok((_, assigned_score)) => {
+            log::info!(
+                "task={task_id} reassignment score {assigned_score} is not better than {score}"
+            );
+            solution.clone()
+        }
+        Err(error) => {
+            log::warn!("task={task_id} reassignment failed: {error}");
+            solution.clone()
+        }
+    };
+    Ok(reassigned)
+}
+
+/// Exchanges the positions of musicians with different instruments while it improves the score.
+///
+/// Every candidate swap is applied to the `ScoreState` and undone if it doesn't help, so a try
+/// costs O(A) per musician whose `qi` changes instead of a full evaluation.
+pub fn swap_search(
+    task_id: usize,
+    rules: ScoringRules,
+    prob: &Problem,
+    solution: &Solution,
+    max_secs: u64,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
+    let mut state = ScoreState::new(rules, prob, solution);
+    let initial_score = state.score();
+    let n = prob.musicians.len();
+    let start = Instant::now();
+    let mut pass = 0;
+    let mut improved = true;
+    'passes: while improved {
+        improved = false;
+        pass += 1;
+        for first in 0..n {
+            for second in (first + 1)..n {
+                if prob.musicians[first] == prob.musicians[second] {
+                    continue;
+                }
+                if start.elapsed().as_secs() > max_secs {
+                    log::info!("task={task_id} pass={pass} swap time limit reached");
+                    break 'passes;
+                }
+                if stop::requested() {
+                    log::info!("task={task_id} pass={pass} swap stop requested");
+                    break 'passes;
+                }
+                let old_score = state.score();
+                state.swap_musicians(first, second);
+                if state.score() > old_score {
+                    improved = true;
+                    log::info!(
+                        "task={task_id} pass={pass} swapped musicians {first} and {second}, score={}",
+                        state.score()
+                    );
+                } else {
+                    state.swap_musicians(first, second);
+                }
             }
-        });
-        log::info!("Musician {} has impact {}", musician_idx, total);
-        res.volumes[musician_idx] = if total > 0.0 { 10.0 } else { 0.0 }
+        }
+    }
+    log::info!(
+        "task={task_id} swaps improved score from {initial_score} to {}",
+        state.score()
+    );
+    Ok(state.to_solution())
+}
+
+/// Picks the volume of every musician that maximizes its exact contribution under the rules.
+///
+/// Volumes change neither blocking (silent musicians still block) nor `qi`, so each musician
+/// is optimized on its own while `ScoreState` keeps the total exact after every change.
+pub fn update_volume(rules: ScoringRules, p: &Problem, s: &Solution) -> anyhow::Result<Solution> {
+    ensure_complete(p, s)?;
+    let mut state = ScoreState::new(rules, p, s);
+    for musician_idx in 0..p.musicians.len() {
+        state.set_volume(musician_idx, 1.0);
+    }
+    let default_score = state.score();
+    for musician_idx in 0..p.musicians.len() {
+        let mut best_volume = 1.0;
+        let mut best_impact = state.musician_score(musician_idx);
+        let best = best_volume(&state.audible_impacts(musician_idx));
+        for volume in [0.0, MAX_VOLUME, best] {
+            state.set_volume(musician_idx, volume);
+            let impact = state.musician_score(musician_idx);
+            if impact > best_impact {
+                best_volume = volume;
+                best_impact = impact;
+            }
+        }
+        state.set_volume(musician_idx, best_volume);
+        log::info!("Musician {musician_idx} has volume {best_volume} and impact {best_impact}");
+    }
+    let score = state.score();
+    log::info!(
+        "Updated volumes. Score with all volumes 1: {default_score}, final score: {score}, gain: {}",
+        score - default_score
+    );
+    Ok(state.to_solution())
+}
+
+// `Σ ceil(v * c)`, the contribution at volume `v` of a musician with audible impacts `c`
+fn volume_score(impacts: &[f64], volume: f64) -> f64 {
+    impacts.iter().map(|c| (volume * c).ceil()).sum()
+}
+
+// The volume in `0..=MAX_VOLUME` with the largest `volume_score`.
+//
+// The score is piecewise constant: a positive `c` raises it just past every `k / c`, a negative
+// one lowers it at every `-k / c`, which can be far too many breakpoints to enumerate. Instead
+// the volume range is bisected, dropping every interval whose bound (positive impacts at its
+// right end, negative ones at its left end) can't beat the best volume seen so far. Breakpoints
+// closer than `MIN_VOLUME_STEP` may be merged.
+fn best_volume(impacts: &[f64]) -> f64 {
+    let bound = |lo: f64, hi: f64| -> f64 {
+        impacts
+            .iter()
+            .map(|c| {
+                let volume = if *c > 0.0 { hi } else { lo };
+                (volume * c).ceil()
+            })
+            .sum()
+    };
+    let mut best = (0.0, volume_score(impacts, 0.0));
+    let mut intervals = vec![(0.0, MAX_VOLUME)];
+    while let Some((lo, hi)) = intervals.pop() {
+        let mid = (lo + hi) / 2.0;
+        for volume in [mid, hi] {
+            let score = volume_score(impacts, volume);
+            if score > best.1 {
+                best = (volume, score);
+            }
+        }
+        if hi - lo > MIN_VOLUME_STEP && bound(lo, hi) > best.1 {
+            intervals.push((mid, hi));
+            intervals.push((lo, mid));
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        model::problem::{Attendee, Position, Problem, Solution},
+        progress::Progress,
+        random_solution::{
+            best_volume, improve_solution, perturb, random_iteration, swap_search, update_volume,
+            volume_score,
+        },
+        scoring::{is_valid_placement, ScoringRules},
+    };
+
+    fn problem() -> Problem {
+        Problem {
+            room_width: 300.0,
+            room_height: 300.0,
+            stage_width: 60.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0, 1, 0],
+            attendees: vec![Attendee {
+                x: 130.0,
+                y: 200.0,
+                tastes: vec![1000.0, -10.0],
+            }],
+            pillars: vec![],
+        }
+    }
+
+    #[test]
+    pub fn random_iteration_is_valid() {
+        let prob = problem();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let solution = random_iteration(&mut rng, &prob, None).unwrap();
+            assert!(is_valid_placement(&prob, &solution));
+        }
+        let crowded = Problem {
+            musicians: vec![0; 20],
+            ..problem()
+        };
+        assert!(random_iteration(&mut rng, &crowded, None).is_err());
+    }
+
+    #[test]
+    pub fn perturbed_solution_is_valid() {
+        let prob = problem();
+        let mut rng = StdRng::seed_from_u64(0);
+        let solution = random_iteration(&mut rng, &prob, None).unwrap();
+        let perturbed = perturb(&mut rng, &prob, &solution, 2);
+        assert!(is_valid_placement(&prob, &perturbed));
+        let n_moved = solution
+            .placements
+            .iter()
+            .zip(&perturbed.placements)
+            .filter(|(before, after)| (before.x, before.y) != (after.x, after.y))
+            .count();
+        assert!((1..=2).contains(&n_moved));
+    }
+
+    #[test]
+    pub fn best_volume_is_found_past_the_first_drop() {
+        // just below the first drop of the negative impact at 2/3 the score is 2, the best score
+        // 3 is first reached just below its second drop at 4/3
+        let impacts = [-1.5, 0.8, 0.8];
+        let volume = best_volume(&impacts);
+        assert!(volume > 1.25 && volume < 4.0 / 3.0);
+        assert_eq!(volume_score(&impacts, volume), 3.0);
+        assert_eq!(best_volume(&[2.0, 3.0]), 10.0);
+        assert_eq!(best_volume(&[-2.0, -3.0]), 0.0);
+    }
+
+    #[test]
+    pub fn incomplete_solution_is_an_error() {
+        let prob = problem();
+        let solution = Solution::new(vec![
+            Position::new(110.0, 110.0),
+            Position::new(130.0, 110.0),
+        ]);
+        let rules = ScoringRules::Lightning;
+        let progress = Progress::default();
+        assert!(improve_solution(0, rules, &prob, &solution, 1.0, 10, 10, None, progress).is_err());
+        assert!(swap_search(0, rules, &prob, &solution, 10).is_err());
+        assert!(update_volume(rules, &prob, &solution).is_err());
     }
-    // let score1 = evaluate_exact(p, &res);
-    // log::info!("Updating volumes. Final score: {}", score1);
-    res
 }
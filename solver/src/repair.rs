@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use float_ord::FloatOrd;
+use memegeom::primitive::{point::Pt, pt};
+
+use crate::{
+    model::problem::{Problem, Solution},
+    scoring::{pt_to_pos, score_breakdown, ScoringRules, BOUND_MIN_DIST},
+    validation::MAX_VOLUME,
+};
+
+// Moved musicians are put slightly farther than the minimum distance to survive rounding
+const REPAIR_EPS: f64 = 1e-6;
+// Rings around the original position are this far apart near it, and farther apart away from it
+const MIN_RING_STEP: f64 = 0.5;
+const RINGS_PER_DOUBLING: f64 = 40.0;
+
+/// Turns any solution into a valid one.
+///
+/// Musicians are considered in order of their contribution to the score: a musician stays where
+/// it is (pulled back inside the stage margin if needed) unless it is too close to a musician
+/// that contributes more. Displaced musicians go to the nearest legal point around their original
+/// position. Missing or NaN placements are put near the stage center, missing volumes are 1.
+///
+/// Musicians sharing their center with another one would get an infinite `qi`, so only the
+/// musicians with distinct placements are scored, the missing and stacked ones go last.
+pub fn repair(
+    rules: ScoringRules,
+    problem: &Problem,
+    solution: &Solution,
+) -> anyhow::Result<Solution> {
+    let n = problem.musicians.len();
+    let left = problem.stage_bottom_left[0] + BOUND_MIN_DIST;
+    let bottom = problem.stage_bottom_left[1] + BOUND_MIN_DIST;
+    let right = problem.stage_bottom_left[0] + problem.stage_width - BOUND_MIN_DIST;
+    let top = problem.stage_bottom_left[1] + problem.stage_height - BOUND_MIN_DIST;
+    if left > right || bottom > top {
+        anyhow::bail!("stage is too small for a single musician");
+    }
+    let center = pt((left + right) / 2.0, (bottom + top) / 2.0);
+
+    let mut is_missing = vec![false; n];
+    let placements: Vec<Pt> = (0..n)
+        .map(|idx| match solution.placements.get(idx) {
+            Some(p) if !p.x.is_nan() && !p.y.is_nan() => {
+                pt(p.x.clamp(left, right), p.y.clamp(bottom, top))
+            }
+            _ => {
+                is_missing[idx] = true;
+                center
+            }
+        })
+        .collect();
+    let volumes: Vec<f64> = (0..n)
+        .map(|idx| match solution.volumes.get(idx) {
+            Some(v) if !v.is_nan() => v.clamp(0.0, MAX_VOLUME),
+            _ => 1.0,
+        })
+        .collect();
+    let mut result = Solution {
+        placements: placements.iter().map(pt_to_pos).collect(),
+        volumes,
+    };
+
+    let mut n_at: HashMap<(u64, u64), usize> = HashMap::new();
+    for p in &placements {
+        *n_at.entry((p.x.to_bits(), p.y.to_bits())).or_default() += 1;
+    }
+    let is_scored: Vec<bool> = (0..n)
+        .map(|idx| {
+            let p = placements[idx];
+            !is_missing[idx] && n_at[&(p.x.to_bits(), p.y.to_bits())] == 1
+        })
+        .collect();
+    let scored: Vec<usize> = (0..n).filter(|idx| is_scored[*idx]).collect();
+    let scored_problem = Problem {
+        musicians: scored.iter().map(|idx| problem.musicians[*idx]).collect(),
+        ..problem.clone()
+    };
+    let scored_solution = Solution {
+        placements: scored
+            .iter()
+            .map(|idx| result.placements[*idx].clone())
+            .collect(),
+        volumes: scored.iter().map(|idx| result.volumes[*idx]).collect(),
+    };
+    let mut contributions = vec![0.0; n];
+    let breakdown = score_breakdown(rules, &scored_problem, &scored_solution);
+    for (idx, contribution) in scored.iter().zip(breakdown.musicians) {
+        contributions[*idx] = contribution;
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|idx| (!is_scored[*idx], FloatOrd(-contributions[*idx])));
+
+    let mut occupancy = Occupancy::default();
+    let mut displaced = Vec::new();
+    for idx in order {
+        if !is_missing[idx] && occupancy.is_free(&placements[idx], BOUND_MIN_DIST) {
+            occupancy.insert(placements[idx]);
+        } else {
+            displaced.push(idx);
+        }
+    }
+    let max_radius = (right - left).hypot(top - bottom);
+    for idx in displaced {
+        let origin = placements[idx];
+        let p = nearest_free(&occupancy, origin, max_radius, |p| {
+            (left..=right).contains(&p.x) && (bottom..=top).contains(&p.y)
+        })
+        .ok_or_else(|| anyhow::anyhow!("no room left on the stage for musician {idx}"))?;
+        log::info!("repair: moving musician {idx} from {origin} to {p}");
+        occupancy.insert(p);
+        result.placements[idx] = pt_to_pos(&p);
+    }
+    Ok(result)
+}
+
+fn nearest_free<F>(occupancy: &Occupancy, origin: Pt, max_radius: f64, on_stage: F) -> Option<Pt>
+where
+    F: Fn(&Pt) -> bool,
+{
+    let min_dist = BOUND_MIN_DIST + REPAIR_EPS;
+    if on_stage(&origin) && occupancy.is_free(&origin, min_dist) {
+        return Some(origin);
+    }
+    let mut r = MIN_RING_STEP;
+    while r <= max_radius + MIN_RING_STEP {
+        let step = MIN_RING_STEP.max(r / RINGS_PER_DOUBLING);
+        let n_samples = ((2.0 * PI * r / step).ceil() as usize).max(8);
+        let found = (0..n_samples)
+            .map(|i| {
+                let angle = 2.0 * PI * (i as f64) / (n_samples as f64);
+                pt(origin.x + r * angle.cos(), origin.y + r * angle.sin())
+            })
+            .find(|p| on_stage(p) && occupancy.is_free(p, min_dist));
+        if found.is_some() {
+            return found;
+        }
+        r += step;
+    }
+    None
+}
+
+/// Placed musicians bucketed by `BOUND_MIN_DIST` cells, so that a collision check only looks at
+/// the neighbouring cells.
+#[derive(Default)]
+struct Occupancy {
+    cells: HashMap<(i64, i64), Vec<Pt>>,
+}
+
+impl Occupancy {
+    fn cell(p: &Pt) -> (i64, i64) {
+        (
+            (p.x / BOUND_MIN_DIST).floor() as i64,
+            (p.y / BOUND_MIN_DIST).floor() as i64,
+        )
+    }
+
+    fn insert(&mut self, p: Pt) {
+        self.cells.entry(Self::cell(&p)).or_default().push(p);
+    }
+
+    // `min_dist` must not exceed twice the cell size
+    fn is_free(&self, p: &Pt, min_dist: f64) -> bool {
+        let (cx, cy) = Self::cell(p);
+        (cx - 1..=cx + 1)
+            .flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .all(|other| (other.x - p.x).hypot(other.y - p.y) >= min_dist)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model::problem::{Attendee, Position, Problem, Solution},
+        repair::repair,
+        scoring::{is_valid_placement, ScoringRules},
+    };
+
+    fn problem() -> Problem {
+        Problem {
+            room_width: 300.0,
+            room_height: 300.0,
+            stage_width: 60.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0, 1, 0, 1],
+            attendees: vec![Attendee {
+                x: 130.0,
+                y: 200.0,
+                tastes: vec![1000.0, 10.0],
+            }],
+            pillars: vec![],
+        }
+    }
+
+    #[test]
+    pub fn valid_solution_is_unchanged() {
+        let placements = vec![
+            Position::new(110.0, 110.0),
+            Position::new(120.0, 110.0),
+            Position::new(130.0, 110.0),
+            Position::new(150.0, 130.0),
+        ];
+        let solution = Solution::new(placements.clone());
+        let repaired = repair(ScoringRules::Lightning, &problem(), &solution).unwrap();
+        for (before, after) in placements.iter().zip(&repaired.placements) {
+            assert_eq!((before.x, before.y), (after.x, after.y));
+        }
+    }
+
+    #[test]
+    pub fn invalid_solution_is_repaired() {
+        let prob = problem();
+        let solution = Solution {
+            placements: vec![
+                // violinists the attendee likes, too close to each other
+                Position::new(125.0, 125.0),
+                Position::new(130.0, 105.0),
+                Position::new(133.0, 125.0),
+                Position::new(f64::NAN, 120.0),
+            ],
+            volumes: vec![1.0, 1.0, 1.0],
+        };
+        let repaired = repair(ScoringRules::Lightning, &prob, &solution).unwrap();
+        assert!(is_valid_placement(&prob, &repaired));
+        assert_eq!(repaired.volumes.len(), 4);
+        // the bass is only pulled back from the edge
+        assert_eq!(
+            (repaired.placements[1].x, repaired.placements[1].y),
+            (130.0, 110.0)
+        );
+        // of the two violinists the one closer to the attendee stays
+        assert_eq!(
+            (repaired.placements[2].x, repaired.placements[2].y),
+            (133.0, 125.0)
+        );
+    }
+
+    #[test]
+    pub fn stacked_musicians_go_last() {
+        let prob = Problem {
+            musicians: vec![0, 0, 0],
+            ..problem()
+        };
+        // the stacked violinists would have an infinite `qi` under the full rules
+        let solution = Solution::new(vec![
+            Position::new(130.0, 125.0),
+            Position::new(130.0, 125.0),
+            Position::new(133.0, 125.0),
+        ]);
+        let repaired = repair(ScoringRules::Full, &prob, &solution).unwrap();
+        assert!(is_valid_placement(&prob, &repaired));
+        assert_eq!(
+            (repaired.placements[2].x, repaired.placements[2].y),
+            (133.0, 125.0)
+        );
+    }
+
+    #[test]
+    pub fn crowded_stage_is_an_error() {
+        let prob = Problem {
+            musicians: vec![0; 20],
+            ..problem()
+        };
+        let solution = Solution::new(vec![Position::new(130.0, 120.0); 20]);
+        assert!(repair(ScoringRules::Lightning, &prob, &solution).is_err());
+    }
+}
@@ -1,13 +1,16 @@
+use std::path::Path;
+
 use crate::model::problem::Attendee;
 use crate::{
-    geometry::{is_blocking, is_blocking_radius},
+    geometry::{is_blocking, is_blocking_radius, BLOCKING_DISTANCE},
     model::problem::{Position, Problem, Solution},
+    visibility::{blocked_targets, blocker_counts, Blocker},
 };
 use memegeom::{
     geom::distance::pt_pt_dist,
     primitive::{point::Pt, pt, segment::Segment, seg},
 };
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 pub fn evaluate_fast(problem: &Problem, solution: &Solution) -> f64 {
     let mut result = 0.0;
@@ -46,37 +49,74 @@ pub fn evaluate_fast(problem: &Problem, solution: &Solution) -> f64 {
     result
 }
 
-pub fn evaluate_exact(problem: &Problem, solution: &Solution) -> f64 {
-    let full = problem.pillars.len() > 0;
-    evaluate_exact_full(full, problem, solution)
+/// Which rules a problem is scored by.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoringRules {
+    /// Only musicians block sound, no closeness factor
+    Lightning,
+    /// Pillars also block sound and `qi` rewards playing close to the same instrument
+    Full,
+}
+
+/// Problems up to this id come from the lightning round
+pub const LAST_LIGHTNING_PROBLEM: u32 = 55;
+
+impl ScoringRules {
+    pub fn from_problem_id(id: u32) -> Self {
+        if id <= LAST_LIGHTNING_PROBLEM {
+            ScoringRules::Lightning
+        } else {
+            ScoringRules::Full
+        }
+    }
+
+    /// Rules of a problem file named after its id, e.g. `../problems/56.json`
+    pub fn from_problem_path(path: &Path) -> Option<Self> {
+        let id = path.file_stem()?.to_str()?.parse().ok()?;
+        Some(Self::from_problem_id(id))
+    }
+
+    pub fn is_full(self) -> bool {
+        self == ScoringRules::Full
+    }
 }
 
-pub fn evaluate_exact_full(full: bool, problem: &Problem, solution: &Solution) -> f64 {
+pub fn evaluate_exact(rules: ScoringRules, problem: &Problem, solution: &Solution) -> f64 {
+    let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let qi = closeness_factors(rules, problem, solution);
     let mut result = 0.0;
     for attendee in &problem.attendees {
-        result += evaluate(full, problem, solution, attendee);
+        result += evaluate(rules, problem, solution, &placements, &qi, attendee);
     }
     result
 }
 
-pub fn is_att_mus_audible(problem: &Problem, solution: &Solution, musician_idx: usize, att_mus_seg: &Segment) -> bool {
-    let full = problem.pillars.len() > 0;
+pub fn is_att_mus_audible(
+    rules: ScoringRules,
+    problem: &Problem,
+    solution: &Solution,
+    musician_idx: usize,
+    att_mus_seg: &Segment,
+) -> bool {
     let is_blocked = (0..problem.musicians.len()).any(|blocker_idx| {
         blocker_idx != musician_idx
             && is_blocking(
-                &att_mus_seg,
+                att_mus_seg,
                 &pt(
                     solution.placements[blocker_idx].x,
                     solution.placements[blocker_idx].y,
                 ),
             )
     });
-    let is_blocked_pillar = if !full {
+    let is_blocked_pillar = if !rules.is_full() {
         false
     } else {
         (0..problem.pillars.len()).any(|blocker_idx| {
             is_blocking_radius(
-                &att_mus_seg,
+                att_mus_seg,
                 &pt(
                     problem.pillars[blocker_idx].center[0],
                     problem.pillars[blocker_idx].center[1],
@@ -88,44 +128,80 @@ pub fn is_att_mus_audible(problem: &Problem, solution: &Solution, musician_idx:
     !is_blocked && !is_blocked_pillar
 }
 
-fn evaluate(full: bool, problem: &Problem, solution: &Solution, attendee: &Attendee) -> f64 {
+/// Closeness factor `qi` of every musician, always 1.0 without the full rules.
+pub fn closeness_factors(rules: ScoringRules, problem: &Problem, solution: &Solution) -> Vec<f64> {
+    (0..problem.musicians.len())
+        .map(|musician_idx| {
+            if !rules.is_full() {
+                1.0
+            } else {
+                (0..problem.musicians.len()).fold(1.0, |s, other_idx| {
+                    if musician_idx == other_idx
+                        || problem.musicians[musician_idx] != problem.musicians[other_idx]
+                    {
+                        s
+                    } else {
+                        let m1 = pos_to_pt(&solution.placements[musician_idx]);
+                        let m2 = pos_to_pt(&solution.placements[other_idx]);
+                        s + 1.0 / pt_pt_dist(&m1, &m2)
+                    }
+                })
+            }
+        })
+        .collect()
+}
+
+pub fn musician_blockers(placements: &[Pt]) -> Vec<Blocker> {
+    placements
+        .iter()
+        .map(|p| Blocker::new(*p, BLOCKING_DISTANCE))
+        .collect()
+}
+
+pub fn pillar_blockers(problem: &Problem) -> Vec<Blocker> {
+    problem
+        .pillars
+        .iter()
+        .map(|pillar| Blocker::new(pt(pillar.center[0], pillar.center[1]), pillar.radius))
+        .collect()
+}
+
+/// Audibility of every musician for the attendee, same as `is_att_mus_audible` for each of them.
+pub fn audible_musicians(
+    rules: ScoringRules,
+    problem: &Problem,
+    placements: &[Pt],
+    attendee: &Attendee,
+) -> Vec<bool> {
+    // pillars go after the musicians, so they never match a musician index
+    let mut blockers = musician_blockers(placements);
+    if rules.is_full() {
+        blockers.extend(pillar_blockers(problem));
+    }
+    blocked_targets(pt(attendee.x, attendee.y), placements, &blockers, true)
+        .into_iter()
+        .map(|is_blocked| !is_blocked)
+        .collect()
+}
+
+fn evaluate(
+    rules: ScoringRules,
+    problem: &Problem,
+    solution: &Solution,
+    placements: &[Pt],
+    qi: &[f64],
+    attendee: &Attendee,
+) -> f64 {
+    let a = pt(attendee.x, attendee.y);
+    let audible = audible_musicians(rules, problem, placements, attendee);
     let mut result = 0.0;
     for musician_idx in 0..problem.musicians.len() {
-        let att_mus_seg = seg(
-            pt(attendee.x, attendee.y),
-            pt(
-                solution.placements[musician_idx].x,
-                solution.placements[musician_idx].y,
-            ),
-        );
-        let is_audible = is_att_mus_audible(problem, solution, musician_idx, &att_mus_seg);
-        let qi = if !full {
-            1.0
-        } else {
-            (0..problem.musicians.len()).fold(1.0, |s, other_idx| {
-                if musician_idx == other_idx
-                    || problem.musicians[musician_idx] != problem.musicians[other_idx]
-                {
-                    s
-                } else {
-                    let m1 = pt(
-                        solution.placements[musician_idx].x,
-                        solution.placements[musician_idx].y,
-                    );
-                    let m2 = pt(
-                        solution.placements[other_idx].x,
-                        solution.placements[other_idx].y,
-                    );
-                    s + 1.0 / pt_pt_dist(&m1, &m2)
-                }
-            })
-        };
         let vol = solution.volumes[musician_idx];
-        if is_audible {
+        if audible[musician_idx] {
             result += impact(
                 vol,
-                qi,
-                pt_pt_dist(&att_mus_seg.st(), &att_mus_seg.en()),
+                qi[musician_idx],
+                pt_pt_dist(&a, &placements[musician_idx]),
                 attendee.tastes[problem.musicians[musician_idx] as usize],
             );
         }
@@ -133,21 +209,569 @@ fn evaluate(full: bool, problem: &Problem, solution: &Solution, attendee: &Atten
     result
 }
 
-pub fn parallel_evaluate_exact(problem: &Problem, solution: &Solution) -> f64 {
-    let full = problem.pillars.len() > 0;
-    parallel_evaluate_exact_full(full, problem, solution)
-}
-
-pub fn parallel_evaluate_exact_full(full: bool, problem: &Problem, solution: &Solution) -> f64 {
+pub fn parallel_evaluate_exact(rules: ScoringRules, problem: &Problem, solution: &Solution) -> f64 {
+    let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let qi = closeness_factors(rules, problem, solution);
     let result = problem
         .attendees
         .as_slice()
         .par_iter()
-        .map(|attendee: &Attendee| evaluate(full, problem, solution, attendee))
+        .map(|attendee: &Attendee| evaluate(rules, problem, solution, &placements, &qi, attendee))
         .sum();
     result
 }
 
+/// Where the score of a solution comes from.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ScoreBreakdown {
+    pub score: f64,
+    /// contribution of each musician over all attendees
+    pub musicians: Vec<f64>,
+    /// contribution of all musicians to each attendee
+    pub attendees: Vec<f64>,
+    /// contribution of the musicians playing each instrument
+    pub instruments: Vec<f64>,
+    /// closeness factor of each musician
+    pub qi: Vec<f64>,
+    /// attendee-musician pairs blocked by other musicians
+    pub blocked_by_musician: usize,
+    /// attendee-musician pairs blocked by pillars, a pair can be blocked by both
+    pub blocked_by_pillar: usize,
+}
+
+pub fn score_breakdown(
+    rules: ScoringRules,
+    problem: &Problem,
+    solution: &Solution,
+) -> ScoreBreakdown {
+    let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let qi = closeness_factors(rules, problem, solution);
+    let musicians = musician_blockers(&placements);
+    let pillars = if rules.is_full() {
+        pillar_blockers(problem)
+    } else {
+        vec![]
+    };
+    let rows: Vec<(Vec<f64>, usize, usize)> = problem
+        .attendees
+        .par_iter()
+        .map(|attendee| {
+            let a = pt(attendee.x, attendee.y);
+            let by_musician = blocked_targets(a, &placements, &musicians, true);
+            let by_pillar = blocked_targets(a, &placements, &pillars, false);
+            let contributions = (0..placements.len())
+                .map(|musician_idx| {
+                    if by_musician[musician_idx] || by_pillar[musician_idx] {
+                        0.0
+                    } else {
+                        impact(
+                            solution.volumes[musician_idx],
+                            qi[musician_idx],
+                            pt_pt_dist(&a, &placements[musician_idx]),
+                            attendee.tastes[problem.musicians[musician_idx] as usize],
+                        )
+                    }
+                })
+                .collect();
+            let n_by_musician = by_musician.iter().filter(|b| **b).count();
+            let n_by_pillar = by_pillar.iter().filter(|b| **b).count();
+            (contributions, n_by_musician, n_by_pillar)
+        })
+        .collect();
+
+    let n_instruments = problem
+        .musicians
+        .iter()
+        .max()
+        .map_or(0, |i| *i as usize + 1);
+    let mut breakdown = ScoreBreakdown {
+        score: 0.0,
+        musicians: vec![0.0; placements.len()],
+        attendees: Vec::with_capacity(rows.len()),
+        instruments: vec![0.0; n_instruments],
+        qi,
+        blocked_by_musician: 0,
+        blocked_by_pillar: 0,
+    };
+    for (contributions, n_by_musician, n_by_pillar) in rows {
+        for (musician_idx, contribution) in contributions.iter().enumerate() {
+            breakdown.musicians[musician_idx] += contribution;
+            breakdown.instruments[problem.musicians[musician_idx] as usize] += contribution;
+        }
+        let attendee_total: f64 = contributions.iter().sum();
+        breakdown.attendees.push(attendee_total);
+        breakdown.score += attendee_total;
+        breakdown.blocked_by_musician += n_by_musician;
+        breakdown.blocked_by_pillar += n_by_pillar;
+    }
+    breakdown
+}
+
+/// Incrementally maintained score of a placement.
+///
+/// Keeps every (attendee, musician) impact, the number of musicians standing on each
+/// attendee→musician line, pillar blocking and the closeness factors `qi`, so that moving a
+/// single musician costs O(A·M) instead of a full O(A·M²) `evaluate_exact`.
+/// Pair data is stored row-major by attendee: index `attendee_idx * n_musicians + musician_idx`.
+pub struct ScoreState<'a> {
+    problem: &'a Problem,
+    rules: ScoringRules,
+    placements: Vec<Pt>,
+    /// unplaced musicians neither play nor block, nor count for `qi`
+    placed: Vec<bool>,
+    volumes: Vec<f64>,
+    /// `ceil(IMPACT_SCALING_COEF * taste / d²)`, i.e. the impact before volume and `qi`
+    impacts: Vec<f64>,
+    /// number of other musicians blocking the attendee→musician line
+    blockers: Vec<u32>,
+    blocked_by_pillar: Vec<bool>,
+    qi: Vec<f64>,
+    musician_scores: Vec<f64>,
+    score: f64,
+}
+
+impl<'a> ScoreState<'a> {
+    pub fn new(rules: ScoringRules, problem: &'a Problem, solution: &Solution) -> Self {
+        let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+        let musicians = musician_blockers(&placements);
+        let pillars = if rules.is_full() {
+            pillar_blockers(problem)
+        } else {
+            vec![]
+        };
+        let rows: Vec<(Vec<f64>, Vec<u32>, Vec<bool>)> = problem
+            .attendees
+            .par_iter()
+            .map(|attendee| {
+                let a = pt(attendee.x, attendee.y);
+                let impacts = (0..placements.len())
+                    .map(|musician_idx| {
+                        base_impact(
+                            problem,
+                            attendee,
+                            musician_idx,
+                            &seg(a, placements[musician_idx]),
+                        )
+                    })
+                    .collect();
+                let blockers = blocker_counts(a, &placements, &musicians, true);
+                let blocked_by_pillar = blocked_targets(a, &placements, &pillars, false);
+                (impacts, blockers, blocked_by_pillar)
+            })
+            .collect();
+        let mut state = Self {
+            problem,
+            rules,
+            qi: vec![1.0; placements.len()],
+            musician_scores: vec![0.0; placements.len()],
+            placed: vec![true; placements.len()],
+            placements,
+            volumes: solution.volumes.clone(),
+            impacts: Vec::with_capacity(rows.len() * problem.musicians.len()),
+            blockers: Vec::with_capacity(rows.len() * problem.musicians.len()),
+            blocked_by_pillar: Vec::with_capacity(rows.len() * problem.musicians.len()),
+            score: 0.0,
+        };
+        for (impacts, blockers, blocked_by_pillar) in rows {
+            state.impacts.extend(impacts);
+            state.blockers.extend(blockers);
+            state.blocked_by_pillar.extend(blocked_by_pillar);
+        }
+        for musician_idx in 0..state.placements.len() {
+            state.qi[musician_idx] = state.compute_qi(musician_idx);
+            state.musician_scores[musician_idx] = state.compute_musician_score(musician_idx);
+        }
+        state.update_total();
+        state
+    }
+
+    /// State with no musician placed yet, all volumes are 1.
+    pub fn empty(rules: ScoringRules, problem: &'a Problem) -> Self {
+        let n = problem.musicians.len();
+        let n_pairs = problem.attendees.len() * n;
+        Self {
+            problem,
+            rules,
+            placements: vec![pt(0.0, 0.0); n],
+            placed: vec![false; n],
+            volumes: vec![1.0; n],
+            impacts: vec![0.0; n_pairs],
+            blockers: vec![0; n_pairs],
+            blocked_by_pillar: vec![false; n_pairs],
+            qi: vec![1.0; n],
+            musician_scores: vec![0.0; n],
+            score: 0.0,
+        }
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn is_placed(&self, musician_idx: usize) -> bool {
+        self.placed[musician_idx]
+    }
+
+    /// Sum of the musician's impacts over all attendees.
+    pub fn musician_score(&self, musician_idx: usize) -> f64 {
+        self.musician_scores[musician_idx]
+    }
+
+    pub fn closeness_factor(&self, musician_idx: usize) -> f64 {
+        self.qi[musician_idx]
+    }
+
+    pub fn position(&self, musician_idx: usize) -> Pt {
+        self.placements[musician_idx]
+    }
+
+    pub fn volume(&self, musician_idx: usize) -> f64 {
+        self.volumes[musician_idx]
+    }
+
+    pub fn is_audible(&self, attendee_idx: usize, musician_idx: usize) -> bool {
+        self.is_audible_at(attendee_idx * self.placements.len() + musician_idx)
+    }
+
+    /// Impact of the musician on the attendee, zero if the line is blocked.
+    pub fn contribution(&self, attendee_idx: usize, musician_idx: usize) -> f64 {
+        let idx = attendee_idx * self.placements.len() + musician_idx;
+        if self.is_audible_at(idx) {
+            self.pair_impact(idx, musician_idx)
+        } else {
+            0.0
+        }
+    }
+
+    /// `qi * impact` for every attendee that hears the musician: the contribution of each of them
+    /// at volume `v` is `ceil(v * qi * impact)`.
+    pub fn audible_impacts(&self, musician_idx: usize) -> Vec<f64> {
+        let n = self.placements.len();
+        (0..self.problem.attendees.len())
+            .map(|attendee_idx| attendee_idx * n + musician_idx)
+            .filter(|&idx| self.is_audible_at(idx))
+            .map(|idx| self.qi[musician_idx] * self.impacts[idx])
+            .collect()
+    }
+
+    /// The solution of a state with every musician placed.
+    pub fn to_solution(&self) -> Solution {
+        Solution {
+            placements: self.placements.iter().map(pt_to_pos).collect(),
+            volumes: self.volumes.clone(),
+        }
+    }
+
+    pub fn set_volume(&mut self, musician_idx: usize, volume: f64) {
+        self.volumes[musician_idx] = volume;
+        self.musician_scores[musician_idx] = self.compute_musician_score(musician_idx);
+        self.update_total();
+    }
+
+    /// Moves one musician and updates everything that depends on its position in O(A·M).
+    pub fn move_musician(&mut self, musician_idx: usize, p: Pt) {
+        debug_assert!(self.placed[musician_idx]);
+        let old = self.placements[musician_idx];
+        self.relocate(musician_idx, Some(old), Some(p));
+    }
+
+    /// Takes a placed musician off the stage, in O(A·M) like `move_musician`: it neither plays
+    /// nor blocks until it is placed again.
+    pub fn remove(&mut self, musician_idx: usize) {
+        debug_assert!(self.placed[musician_idx]);
+        self.placed[musician_idx] = false;
+        let old = self.placements[musician_idx];
+        self.relocate(musician_idx, Some(old), None);
+    }
+
+    /// Puts an unplaced musician on the stage, in O(A·M) like `move_musician`.
+    pub fn place(&mut self, musician_idx: usize, p: Pt) {
+        debug_assert!(!self.placed[musician_idx]);
+        self.placed[musician_idx] = true;
+        self.relocate(musician_idx, None, Some(p));
+    }
+
+    /// Exact score change of `place(musician_idx, p)` without changing the state: what the
+    /// musician plays, minus the pairs it blocks, plus the `qi` changes of its instrument.
+    pub fn placement_gain(&self, musician_idx: usize, p: Pt) -> f64 {
+        debug_assert!(!self.placed[musician_idx]);
+        let n = self.placements.len();
+        let problem = self.problem;
+        let full = self.rules.is_full();
+        let instrument = problem.musicians[musician_idx];
+        let with = Some((musician_idx, p));
+        // new `qi` of the placed musicians that share the instrument, their scores are recomputed
+        let new_qi: Vec<Option<f64>> = (0..n)
+            .map(|other_idx| {
+                (full
+                    && other_idx != musician_idx
+                    && self.placed[other_idx]
+                    && problem.musicians[other_idx] == instrument)
+                    .then(|| self.compute_qi_with(other_idx, with))
+            })
+            .collect();
+        let own_qi = self.compute_qi_with(musician_idx, with);
+        let mut new_scores = vec![0.0; n];
+        let mut gain = 0.0;
+        for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+            let a = pt(attendee.x, attendee.y);
+            let row = attendee_idx * n;
+            for other_idx in 0..n {
+                let idx = row + other_idx;
+                if other_idx == musician_idx || !self.is_audible_at(idx) {
+                    continue;
+                }
+                let is_now_blocked = is_blocking(&seg(a, self.placements[other_idx]), &p);
+                match new_qi[other_idx] {
+                    Some(qi) if !is_now_blocked => {
+                        new_scores[other_idx] +=
+                            (self.volumes[other_idx] * qi * self.impacts[idx]).ceil()
+                    }
+                    Some(_) => {}
+                    None if is_now_blocked => gain -= self.pair_impact(idx, other_idx),
+                    None => {}
+                }
+            }
+            let att_mus_seg = seg(a, p);
+            if count_blockers(&self.placements, &self.placed, musician_idx, &att_mus_seg) == 0
+                && !(full && is_blocked_by_pillar(problem, &att_mus_seg))
+            {
+                let impact = base_impact(problem, attendee, musician_idx, &att_mus_seg);
+                gain += (self.volumes[musician_idx] * own_qi * impact).ceil();
+            }
+        }
+        for (other_idx, qi) in new_qi.iter().enumerate() {
+            if qi.is_some() {
+                gain += new_scores[other_idx] - self.musician_scores[other_idx];
+            }
+        }
+        gain
+    }
+
+    // Updates everything that depends on the musician's position, `old` is `None` if the
+    // musician wasn't placed and blocked nothing, `new` is `None` if it is being removed
+    fn relocate(&mut self, musician_idx: usize, old: Option<Pt>, new: Option<Pt>) {
+        if let Some(p) = new {
+            self.placements[musician_idx] = p;
+        }
+        let n = self.placements.len();
+        let problem = self.problem;
+        let full = self.rules.is_full();
+        // musicians whose qi depends on this position are fully recomputed below
+        let qi_changes = |other_idx: usize| {
+            other_idx == musician_idx
+                || (full && problem.musicians[other_idx] == problem.musicians[musician_idx])
+        };
+        for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+            let a = pt(attendee.x, attendee.y);
+            let row = attendee_idx * n;
+            for other_idx in 0..n {
+                if other_idx == musician_idx || !self.placed[other_idx] {
+                    continue;
+                }
+                let att_mus_seg = seg(a, self.placements[other_idx]);
+                let was_blocking = old.is_some_and(|old| is_blocking(&att_mus_seg, &old));
+                let is_now_blocking = new.is_some_and(|p| is_blocking(&att_mus_seg, &p));
+                if was_blocking == is_now_blocking {
+                    continue;
+                }
+                let idx = row + other_idx;
+                let was_audible = self.is_audible_at(idx);
+                if was_blocking {
+                    self.blockers[idx] -= 1;
+                } else {
+                    self.blockers[idx] += 1;
+                }
+                let is_now_audible = self.is_audible_at(idx);
+                if was_audible != is_now_audible && !qi_changes(other_idx) {
+                    let impact = self.pair_impact(idx, other_idx);
+                    self.musician_scores[other_idx] +=
+                        if is_now_audible { impact } else { -impact };
+                }
+            }
+            let Some(p) = new else {
+                continue;
+            };
+            let att_mus_seg = seg(a, p);
+            let idx = row + musician_idx;
+            self.impacts[idx] = base_impact(problem, attendee, musician_idx, &att_mus_seg);
+            self.blockers[idx] =
+                count_blockers(&self.placements, &self.placed, musician_idx, &att_mus_seg);
+            self.blocked_by_pillar[idx] = full && is_blocked_by_pillar(problem, &att_mus_seg);
+        }
+        for other_idx in 0..n {
+            if qi_changes(other_idx) {
+                self.qi[other_idx] = self.compute_qi(other_idx);
+            }
+        }
+        for other_idx in 0..n {
+            if qi_changes(other_idx) {
+                self.musician_scores[other_idx] = self.compute_musician_score(other_idx);
+            }
+        }
+        self.update_total();
+    }
+
+    /// Exchanges the positions of two musicians, volumes stay with the musicians. The set of
+    /// positions doesn't change, so blocking just moves between the two: O(A) plus O(A) per
+    /// musician whose `qi` changes.
+    pub fn swap_musicians(&mut self, first: usize, second: usize) {
+        debug_assert!(self.placed[first] && self.placed[second]);
+        if first == second {
+            return;
+        }
+        self.placements.swap(first, second);
+        let n = self.placements.len();
+        let problem = self.problem;
+        for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+            let a = pt(attendee.x, attendee.y);
+            let row = attendee_idx * n;
+            self.blockers.swap(row + first, row + second);
+            self.blocked_by_pillar.swap(row + first, row + second);
+            for musician_idx in [first, second] {
+                self.impacts[row + musician_idx] = base_impact(
+                    problem,
+                    attendee,
+                    musician_idx,
+                    &seg(a, self.placements[musician_idx]),
+                );
+            }
+        }
+        let full = self.rules.is_full();
+        let qi_changes = |other_idx: usize| {
+            other_idx == first
+                || other_idx == second
+                || (full
+                    && (problem.musicians[other_idx] == problem.musicians[first]
+                        || problem.musicians[other_idx] == problem.musicians[second]))
+        };
+        for other_idx in 0..n {
+            if qi_changes(other_idx) {
+                self.qi[other_idx] = self.compute_qi(other_idx);
+            }
+        }
+        for other_idx in 0..n {
+            if qi_changes(other_idx) {
+                self.musician_scores[other_idx] = self.compute_musician_score(other_idx);
+            }
+        }
+        self.update_total();
+    }
+
+    /// Analytic gradient of the smooth score w.r.t. the musician's position,
+    /// see `smooth_gradient`.
+    pub fn gradient(&self, musician_idx: usize) -> Pt {
+        smooth_gradient(
+            self.rules,
+            self.problem,
+            &self.placements,
+            &self.volumes,
+            &self.qi,
+            musician_idx,
+            &|attendee_idx, musician_idx| self.is_audible(attendee_idx, musician_idx),
+            &|musician_idx| {
+                smooth_weight(
+                    self.problem,
+                    &self.placements,
+                    musician_idx,
+                    &|attendee_idx| self.is_audible(attendee_idx, musician_idx),
+                )
+            },
+        )
+    }
+
+    fn is_audible_at(&self, idx: usize) -> bool {
+        self.blockers[idx] == 0
+            && !self.blocked_by_pillar[idx]
+            && self.placed[idx % self.placements.len()]
+    }
+
+    fn pair_impact(&self, idx: usize, musician_idx: usize) -> f64 {
+        (self.volumes[musician_idx] * self.qi[musician_idx] * self.impacts[idx]).ceil()
+    }
+
+    // Same summation order as `evaluate`, so that the results are bit-for-bit identical
+    fn compute_qi(&self, musician_idx: usize) -> f64 {
+        self.compute_qi_with(musician_idx, None)
+    }
+
+    // `qi` as if musician `with.0` was placed at `with.1`
+    fn compute_qi_with(&self, musician_idx: usize, with: Option<(usize, Pt)>) -> f64 {
+        if !self.rules.is_full() {
+            return 1.0;
+        }
+        let position = |idx: usize| match with {
+            Some((with_idx, p)) if with_idx == idx => Some(p),
+            _ => self.placed[idx].then(|| self.placements[idx]),
+        };
+        let Some(p) = position(musician_idx) else {
+            return 1.0;
+        };
+        (0..self.placements.len()).fold(1.0, |s, other_idx| {
+            if musician_idx == other_idx
+                || self.problem.musicians[musician_idx] != self.problem.musicians[other_idx]
+            {
+                return s;
+            }
+            match position(other_idx) {
+                Some(other) => s + 1.0 / pt_pt_dist(&p, &other),
+                None => s,
+            }
+        })
+    }
+
+    fn compute_musician_score(&self, musician_idx: usize) -> f64 {
+        let n = self.placements.len();
+        (0..self.problem.attendees.len())
+            .map(|attendee_idx| attendee_idx * n + musician_idx)
+            .filter(|&idx| self.is_audible_at(idx))
+            .map(|idx| self.pair_impact(idx, musician_idx))
+            .sum()
+    }
+
+    fn update_total(&mut self) {
+        self.score = self.musician_scores.iter().sum();
+    }
+}
+
+fn base_impact(
+    problem: &Problem,
+    attendee: &Attendee,
+    musician_idx: usize,
+    att_mus_seg: &Segment,
+) -> f64 {
+    let taste = attendee.tastes[problem.musicians[musician_idx] as usize];
+    let distance = pt_pt_dist(&att_mus_seg.st(), &att_mus_seg.en());
+    (IMPACT_SCALING_COEF * taste / distance.powi(2)).ceil()
+}
+
+fn count_blockers(
+    placements: &[Pt],
+    placed: &[bool],
+    musician_idx: usize,
+    att_mus_seg: &Segment,
+) -> u32 {
+    placements
+        .iter()
+        .enumerate()
+        .filter(|(blocker_idx, blocker)| {
+            *blocker_idx != musician_idx
+                && placed[*blocker_idx]
+                && is_blocking(att_mus_seg, blocker)
+        })
+        .count() as u32
+}
+
+fn is_blocked_by_pillar(problem: &Problem, att_mus_seg: &Segment) -> bool {
+    problem.pillars.iter().any(|pillar| {
+        is_blocking_radius(
+            att_mus_seg,
+            &pt(pillar.center[0], pillar.center[1]),
+            pillar.radius,
+        )
+    })
+}
+
 pub const IMPACT_SCALING_COEF: f64 = 1_000_000.0;
 
 fn impact(vol: f64, qi: f64, distance: f64, taste: f64) -> f64 {
@@ -188,6 +812,167 @@ pub fn is_valid_placement(problem: &Problem, solution: &Solution) -> bool {
     true
 }
 
+/// Score without the `ceil` roundings, i.e. the sum of `vol * qi * IMPACT_SCALING_COEF * taste / d²`
+/// over audible pairs.
+pub fn evaluate_smooth(rules: ScoringRules, problem: &Problem, solution: &Solution) -> f64 {
+    let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let qi = closeness_factors(rules, problem, solution);
+    let audible = audibility(rules, problem, &placements);
+    (0..placements.len())
+        .map(|musician_idx| {
+            solution.volumes[musician_idx]
+                * qi[musician_idx]
+                * smooth_weight(problem, &placements, musician_idx, &|attendee_idx| {
+                    audible[attendee_idx][musician_idx]
+                })
+        })
+        .sum()
+}
+
+/// Analytic gradient of `evaluate_smooth` w.r.t. every musician's position.
+///
+/// Blocking is treated as a fixed mask: the gradient only accounts for the `1/d²` attenuation and
+/// the `qi` closeness factors.
+pub fn score_gradient(rules: ScoringRules, problem: &Problem, solution: &Solution) -> Vec<Pt> {
+    let placements: Vec<Pt> = solution.placements.iter().map(pos_to_pt).collect();
+    let qi = closeness_factors(rules, problem, solution);
+    let audible = audibility(rules, problem, &placements);
+    let is_audible = |attendee_idx: usize, musician_idx: usize| audible[attendee_idx][musician_idx];
+    let weights: Vec<f64> = (0..placements.len())
+        .into_par_iter()
+        .map(|musician_idx| {
+            smooth_weight(problem, &placements, musician_idx, &|attendee_idx| {
+                is_audible(attendee_idx, musician_idx)
+            })
+        })
+        .collect();
+    (0..placements.len())
+        .into_par_iter()
+        .map(|musician_idx| {
+            smooth_gradient(
+                rules,
+                problem,
+                &placements,
+                &solution.volumes,
+                &qi,
+                musician_idx,
+                &is_audible,
+                &|other_idx| weights[other_idx],
+            )
+        })
+        .collect()
+}
+
+// audibility[attendee_idx][musician_idx]
+fn audibility(rules: ScoringRules, problem: &Problem, placements: &[Pt]) -> Vec<Vec<bool>> {
+    problem
+        .attendees
+        .par_iter()
+        .map(|attendee| audible_musicians(rules, problem, placements, attendee))
+        .collect()
+}
+
+// Sum of `IMPACT_SCALING_COEF * taste / d²` over the attendees that hear the musician
+fn smooth_weight<A>(
+    problem: &Problem,
+    placements: &[Pt],
+    musician_idx: usize,
+    is_audible: &A,
+) -> f64
+where
+    A: Fn(usize) -> bool,
+{
+    let m = placements[musician_idx];
+    problem
+        .attendees
+        .iter()
+        .enumerate()
+        .filter(|(attendee_idx, _)| is_audible(*attendee_idx))
+        .map(|(_, attendee)| {
+            let taste = attendee.tastes[problem.musicians[musician_idx] as usize];
+            let d2 = (m.x - attendee.x).powi(2) + (m.y - attendee.y).powi(2);
+            IMPACT_SCALING_COEF * taste / d2
+        })
+        .sum()
+}
+
+// With W_j the smooth weight of musician j, the smooth score is Σ_j vol_j * qi_j * W_j, so
+//   ∂/∂p_k = vol_k * qi_k * ∂W_k/∂p_k + Σ_{j ≠ k, same instrument} (vol_k * W_k + vol_j * W_j) * ∂(1/|p_k - p_j|)/∂p_k
+#[allow(clippy::too_many_arguments)]
+fn smooth_gradient<A, W>(
+    rules: ScoringRules,
+    problem: &Problem,
+    placements: &[Pt],
+    volumes: &[f64],
+    qi: &[f64],
+    musician_idx: usize,
+    is_audible: &A,
+    weight: &W,
+) -> Pt
+where
+    A: Fn(usize, usize) -> bool,
+    W: Fn(usize) -> f64,
+{
+    let m = placements[musician_idx];
+    let (mut dx, mut dy) = (0.0, 0.0);
+    for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+        if !is_audible(attendee_idx, musician_idx) {
+            continue;
+        }
+        let taste = attendee.tastes[problem.musicians[musician_idx] as usize];
+        let (x, y) = (m.x - attendee.x, m.y - attendee.y);
+        let d2 = x * x + y * y;
+        let c = -2.0 * IMPACT_SCALING_COEF * taste / (d2 * d2);
+        dx += c * x;
+        dy += c * y;
+    }
+    let scale = volumes[musician_idx] * qi[musician_idx];
+    let (mut dx, mut dy) = (scale * dx, scale * dy);
+    if rules.is_full() {
+        let own = volumes[musician_idx] * weight(musician_idx);
+        for other_idx in 0..placements.len() {
+            if other_idx == musician_idx
+                || problem.musicians[other_idx] != problem.musicians[musician_idx]
+            {
+                continue;
+            }
+            let (x, y) = (m.x - placements[other_idx].x, m.y - placements[other_idx].y);
+            let d = (x * x + y * y).sqrt();
+            let c = -(own + volumes[other_idx] * weight(other_idx)) / d.powi(3);
+            dx += c * x;
+            dy += c * y;
+        }
+    }
+    pt(dx, dy)
+}
+
+/// Gradient of `bound_penalty` w.r.t. the musician's position.
+pub fn bound_penalty_gradient(problem: &Problem, solution: &Solution, musician_idx: usize) -> Pt {
+    let bottom_left = pt(problem.stage_bottom_left[0], problem.stage_bottom_left[1]);
+    let top_right = pt(
+        bottom_left.x + problem.stage_width,
+        bottom_left.y + problem.stage_height,
+    );
+    let m = pos_to_pt(&solution.placements[musician_idx]);
+    let mut dx =
+        dist_penalty_derivative(m.x - bottom_left.x) - dist_penalty_derivative(top_right.x - m.x);
+    let mut dy =
+        dist_penalty_derivative(m.y - bottom_left.y) - dist_penalty_derivative(top_right.y - m.y);
+    for (other_idx, other) in solution.placements.iter().enumerate() {
+        if other_idx == musician_idx {
+            continue;
+        }
+        let d = pt_pt_dist(&m, &pos_to_pt(other));
+        if d > 0.0 {
+            // every pair is counted twice in `bound_penalty`
+            let c = 2.0 * dist_penalty_derivative(d) / d;
+            dx += c * (m.x - other.x);
+            dy += c * (m.y - other.y);
+        }
+    }
+    pt(dx, dy)
+}
+
 pub fn bound_penalty(problem: &Problem, solution: &Solution) -> f64 {
     let bottom_left = pt(problem.stage_bottom_left[0], problem.stage_bottom_left[1]);
     let top_right = pt(
@@ -241,6 +1026,15 @@ fn dist_penalty(d: f64) -> f64 {
     BOUND_SCALING_COEF * relu(BOUND_MAX_DIST - d)
 }
 
+// derivative of `dist_penalty` w.r.t. d
+fn dist_penalty_derivative(d: f64) -> f64 {
+    if BOUND_MAX_DIST - d > 0.0 {
+        -BOUND_SCALING_COEF
+    } else {
+        0.0
+    }
+}
+
 fn relu(x: f64) -> f64 {
     if x > 0.0 {
         x
@@ -270,11 +1064,16 @@ where
 
 #[cfg(test)]
 mod test {
-    use memegeom::primitive::pt;
+    use std::path::Path;
 
-    use crate::model::problem::Attendee;
+    use memegeom::primitive::{point::Pt, pt};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::model::problem::{Attendee, Pillar};
     use crate::scoring::{
-        evaluate_exact_full, outside_stage_penalty, Position, Problem, Solution, BOUND_SCALING_COEF,
+        bound_penalty, bound_penalty_gradient, evaluate_exact, evaluate_smooth, grad,
+        outside_stage_penalty, pos_to_pt, pt_to_pos, score_breakdown, score_gradient, Position,
+        Problem, ScoreState, ScoringRules, Solution, BOUND_SCALING_COEF,
     };
 
     #[test]
@@ -369,7 +1168,7 @@ mod test {
     pub fn test_example_score_old_1() {
         let prob = example_problem();
         let sol = example_solution();
-        assert_eq!(evaluate_exact_full(false, &prob, &sol), 5343.0)
+        assert_eq!(evaluate_exact(ScoringRules::Lightning, &prob, &sol), 5343.0)
     }
 
     #[test]
@@ -383,13 +1182,275 @@ mod test {
             ],
             volumes: vec![1.0; 3],
         };
-        assert_eq!(evaluate_exact_full(false, &prob, &sol), 5350.0)
+        assert_eq!(evaluate_exact(ScoringRules::Lightning, &prob, &sol), 5350.0)
     }
 
     #[test]
     pub fn test_example_score_new() {
         let prob = example_problem();
         let sol = example_solution();
-        assert_eq!(evaluate_exact_full(true, &prob, &sol), 5357.0)
+        assert_eq!(evaluate_exact(ScoringRules::Full, &prob, &sol), 5357.0)
+    }
+
+    #[test]
+    pub fn test_rules_from_problem_path() {
+        assert_eq!(
+            ScoringRules::from_problem_path(Path::new("../problems/55.json")),
+            Some(ScoringRules::Lightning)
+        );
+        assert_eq!(
+            ScoringRules::from_problem_path(Path::new("../problems/56.json")),
+            Some(ScoringRules::Full)
+        );
+        assert_eq!(
+            ScoringRules::from_problem_path(Path::new("example.json")),
+            None
+        );
+    }
+
+    #[test]
+    pub fn test_example_score_state() {
+        let prob = example_problem();
+        let sol = example_solution();
+        assert_eq!(
+            ScoreState::new(ScoringRules::Lightning, &prob, &sol).score(),
+            5343.0
+        );
+        assert_eq!(
+            ScoreState::new(ScoringRules::Full, &prob, &sol).score(),
+            5357.0
+        );
+    }
+
+    // A small crowded stage, so that musicians and pillars block each other a lot
+    fn random_problem(rng: &mut StdRng) -> Problem {
+        Problem {
+            room_width: 400.0,
+            room_height: 400.0,
+            stage_width: 100.0,
+            stage_height: 60.0,
+            stage_bottom_left: vec![150.0, 100.0],
+            musicians: (0..12).map(|i| i % 3).collect(),
+            attendees: (0..15)
+                .map(|_| Attendee {
+                    x: rng.gen_range(0.0..400.0),
+                    y: rng.gen_range(200.0..400.0),
+                    tastes: (0..3).map(|_| rng.gen_range(-1000.0..1000.0)).collect(),
+                })
+                .collect(),
+            pillars: (0..3)
+                .map(|_| Pillar {
+                    center: vec![rng.gen_range(150.0..250.0), rng.gen_range(170.0..190.0)],
+                    radius: rng.gen_range(2.0..8.0),
+                })
+                .collect(),
+        }
+    }
+
+    fn random_stage_pt(rng: &mut StdRng, prob: &Problem) -> Pt {
+        pt(
+            prob.stage_bottom_left[0] + rng.gen_range(0.0..prob.stage_width),
+            prob.stage_bottom_left[1] + rng.gen_range(0.0..prob.stage_height),
+        )
+    }
+
+    #[test]
+    pub fn test_score_breakdown() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let prob = random_problem(&mut rng);
+        let sol = Solution::new(
+            (0..prob.musicians.len())
+                .map(|_| {
+                    let p = random_stage_pt(&mut rng, &prob);
+                    Position::new(p.x, p.y)
+                })
+                .collect(),
+        );
+        let breakdown = score_breakdown(ScoringRules::Full, &prob, &sol);
+        assert_eq!(
+            breakdown.score,
+            evaluate_exact(ScoringRules::Full, &prob, &sol)
+        );
+        assert_eq!(breakdown.musicians.iter().sum::<f64>(), breakdown.score);
+        assert_eq!(breakdown.attendees.iter().sum::<f64>(), breakdown.score);
+        assert_eq!(breakdown.instruments.iter().sum::<f64>(), breakdown.score);
+        let state = ScoreState::new(ScoringRules::Full, &prob, &sol);
+        for musician_idx in 0..prob.musicians.len() {
+            assert_eq!(
+                breakdown.musicians[musician_idx],
+                state.musician_score(musician_idx)
+            );
+            assert_eq!(
+                breakdown.qi[musician_idx],
+                state.closeness_factor(musician_idx)
+            );
+        }
+        assert!(breakdown.blocked_by_musician > 0);
+        assert!(breakdown.blocked_by_pillar > 0);
+    }
+
+    #[test]
+    pub fn test_score_state_matches_exact() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = random_problem(&mut rng);
+            let sol = Solution::new(
+                (0..prob.musicians.len())
+                    .map(|_| {
+                        let p = random_stage_pt(&mut rng, &prob);
+                        Position::new(p.x, p.y)
+                    })
+                    .collect(),
+            );
+            let mut state = ScoreState::new(rules, &prob, &sol);
+            assert_eq!(state.score(), evaluate_exact(rules, &prob, &sol));
+            for _ in 0..100 {
+                let idx = rng.gen_range(0..prob.musicians.len());
+                state.move_musician(idx, random_stage_pt(&mut rng, &prob));
+                if rng.gen_bool(0.2) {
+                    state.set_volume(idx, rng.gen_range(0.0..=10.0));
+                }
+                assert_eq!(
+                    state.score(),
+                    evaluate_exact(rules, &prob, &state.to_solution())
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_score_state_swaps_match_exact() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = random_problem(&mut rng);
+            let sol = random_solution(&mut rng, &prob);
+            let mut state = ScoreState::new(rules, &prob, &sol);
+            for _ in 0..50 {
+                let first = rng.gen_range(0..prob.musicians.len());
+                let second = rng.gen_range(0..prob.musicians.len());
+                state.swap_musicians(first, second);
+                assert_eq!(
+                    state.score(),
+                    evaluate_exact(rules, &prob, &state.to_solution())
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_placement_gain_matches_place() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = random_problem(&mut rng);
+            let sol = random_solution(&mut rng, &prob);
+            let mut state = ScoreState::empty(rules, &prob);
+            for (musician_idx, volume) in sol.volumes.iter().enumerate() {
+                state.set_volume(musician_idx, *volume);
+            }
+            assert_eq!(state.score(), 0.0);
+            for musician_idx in (0..prob.musicians.len()).rev() {
+                let p = pos_to_pt(&sol.placements[musician_idx]);
+                let gain = state.placement_gain(musician_idx, p);
+                let before = state.score();
+                state.place(musician_idx, p);
+                assert_eq!(state.score() - before, gain);
+            }
+            assert_eq!(state.score(), evaluate_exact(rules, &prob, &sol));
+        }
+    }
+
+    #[test]
+    pub fn test_remove_matches_placing_the_rest() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = random_problem(&mut rng);
+            let sol = random_solution(&mut rng, &prob);
+            let n = prob.musicians.len();
+            let removed: Vec<usize> = (0..n).filter(|_| rng.gen_bool(0.3)).collect();
+            let mut state = ScoreState::new(rules, &prob, &sol);
+            let mut rest = ScoreState::empty(rules, &prob);
+            for musician_idx in 0..n {
+                rest.set_volume(musician_idx, sol.volumes[musician_idx]);
+                if !removed.contains(&musician_idx) {
+                    rest.place(musician_idx, pos_to_pt(&sol.placements[musician_idx]));
+                }
+            }
+            for musician_idx in &removed {
+                state.remove(*musician_idx);
+            }
+            assert_eq!(state.score(), rest.score());
+            for musician_idx in &removed {
+                state.place(*musician_idx, pos_to_pt(&sol.placements[*musician_idx]));
+            }
+            assert_eq!(state.score(), evaluate_exact(rules, &prob, &sol));
+        }
+    }
+
+    fn random_solution(rng: &mut StdRng, prob: &Problem) -> Solution {
+        Solution {
+            placements: (0..prob.musicians.len())
+                .map(|_| pt_to_pos(&random_stage_pt(rng, prob)))
+                .collect(),
+            volumes: (0..prob.musicians.len())
+                .map(|_| rng.gen_range(0.0..=10.0))
+                .collect(),
+        }
+    }
+
+    fn assert_close(expected: Pt, actual: Pt) {
+        let err = pt(expected.x - actual.x, expected.y - actual.y).mag();
+        assert!(
+            err <= 1e-4 * expected.mag().max(1.0),
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    pub fn test_score_gradient_matches_finite_difference() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for rules in [ScoringRules::Lightning, ScoringRules::Full] {
+            let prob = random_problem(&mut rng);
+            let mut sol = random_solution(&mut rng, &prob);
+            let gradient = score_gradient(rules, &prob, &sol);
+            let state = ScoreState::new(rules, &prob, &sol);
+            for (musician_idx, analytic) in gradient.into_iter().enumerate() {
+                let p = pos_to_pt(&sol.placements[musician_idx]);
+                let expected = grad(
+                    1e-4,
+                    |q| {
+                        sol.placements[musician_idx] = pt_to_pos(q);
+                        let r = evaluate_smooth(rules, &prob, &sol);
+                        sol.placements[musician_idx] = pt_to_pos(&p);
+                        r
+                    },
+                    &p,
+                );
+                assert_close(expected, analytic);
+                assert_close(expected, state.gradient(musician_idx));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_bound_penalty_gradient() {
+        let prob = example_problem();
+        let mut sol = example_solution();
+        // 3 units from the bottom edge and 6 units from the next musician
+        sol.placements[0] = Position::new(1100.0, 3.0);
+        sol.placements[2] = Position::new(1100.0, 106.0);
+        for musician_idx in 0..sol.placements.len() {
+            let p = pos_to_pt(&sol.placements[musician_idx]);
+            let expected = grad(
+                1e-3,
+                |q| {
+                    sol.placements[musician_idx] = pt_to_pos(q);
+                    let r = bound_penalty(&prob, &sol);
+                    sol.placements[musician_idx] = pt_to_pos(&p);
+                    r
+                },
+                &p,
+            );
+            assert_close(expected, bound_penalty_gradient(&prob, &sol, musician_idx));
+        }
     }
 }
@@ -0,0 +1,279 @@
+use memegeom::primitive::{point::Pt, pt};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use rayon::prelude::*;
+
+use crate::{
+    model::problem::Problem,
+    scoring::{pillar_blockers, ScoringRules, BOUND_MIN_DIST, IMPACT_SCALING_COEF},
+    slots::margin_rect,
+    visibility::blocked_targets,
+};
+
+// Cap on the grid points, on large stages the step grows past `BOUND_MIN_DIST` to stay under it
+const MAX_SHADOW_CELLS: usize = 10_000;
+// Share of the `sample` draws that ignore the values, so that points worth nothing still get
+// musicians once the valuable ones are taken
+const UNIFORM_SHARE: f64 = 0.25;
+
+/// Which attendees every point of a grid over the stage margin sees past the pillars, and what
+/// each instrument is worth there counting only those attendees.
+///
+/// A point off the grid takes the visibility of its nearest grid point, so near the edge of a
+/// shadow the map can be off by a cell. It only steers placement, the exact scoring still
+/// decides what is kept.
+pub struct ShadowMap {
+    left: f64,
+    bottom: f64,
+    right: f64,
+    top: f64,
+    step: f64,
+    n_x: usize,
+    n_y: usize,
+    // `words` bits per cell, one per attendee, set if the attendee is visible
+    visible: Vec<u64>,
+    words: usize,
+    // `[instrument][cell]`, the sum of `ceil(IMPACT_SCALING_COEF * taste / d²)` over the visible
+    // attendees
+    values: Vec<Vec<f64>>,
+    // where `sample` puts a musician of each instrument, `None` if it is worth nothing anywhere
+    weights: Vec<Option<WeightedIndex<f64>>>,
+}
+
+/// The shadow map of the problem, `None` if pillars don't block under the rules or there are none.
+pub fn shadow_map(rules: ScoringRules, problem: &Problem) -> Option<ShadowMap> {
+    if !rules.is_full() || problem.pillars.is_empty() {
+        return None;
+    }
+    ShadowMap::new(problem)
+}
+
+impl ShadowMap {
+    /// Sweeps the attendees from every grid point, O(C·(A + P) log(A + P) + C·A·I) for C grid
+    /// points and I instruments. `None` if the stage is too small for a musician.
+    pub fn new(problem: &Problem) -> Option<Self> {
+        let (left, bottom, right, top) = margin_rect(problem)?;
+        let step =
+            BOUND_MIN_DIST.max(((right - left) * (top - bottom) / MAX_SHADOW_CELLS as f64).sqrt());
+        let n_x = ((right - left) / step) as usize + 1;
+        let n_y = ((top - bottom) / step) as usize + 1;
+        let n_instruments = problem
+            .musicians
+            .iter()
+            .map(|i| *i as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let words = problem.attendees.len().div_ceil(64);
+        let attendees: Vec<Pt> = problem.attendees.iter().map(|a| pt(a.x, a.y)).collect();
+        let pillars = pillar_blockers(problem);
+        let cells: Vec<(Vec<u64>, Vec<f64>)> = (0..n_x * n_y)
+            .into_par_iter()
+            .map(|cell| {
+                let p = pt(
+                    left + (cell % n_x) as f64 * step,
+                    bottom + (cell / n_x) as f64 * step,
+                );
+                let mut bits = vec![0u64; words];
+                let mut values = vec![0.0; n_instruments];
+                let blocked = blocked_targets(p, &attendees, &pillars, false);
+                for (attendee_idx, attendee) in problem.attendees.iter().enumerate() {
+                    if blocked[attendee_idx] {
+                        continue;
+                    }
+                    bits[attendee_idx / 64] |= 1 << (attendee_idx % 64);
+                    let d2 = (p.x - attendee.x).powi(2) + (p.y - attendee.y).powi(2);
+                    for (instrument, value) in values.iter_mut().enumerate() {
+                        *value += (IMPACT_SCALING_COEF * attendee.tastes[instrument] / d2).ceil();
+                    }
+                }
+                (bits, values)
+            })
+            .collect();
+        let mut visible = Vec::with_capacity(cells.len() * words);
+        let mut values = vec![Vec::with_capacity(cells.len()); n_instruments];
+        for (bits, cell_values) in cells {
+            visible.extend(bits);
+            for (row, value) in values.iter_mut().zip(cell_values) {
+                row.push(value);
+            }
+        }
+        let weights = values
+            .iter()
+            .map(|row| WeightedIndex::new(row.iter().map(|v| v.max(0.0))).ok())
+            .collect();
+        log::info!(
+            "shadow map: {n_x}x{n_y} points with step {step} for {} pillars",
+            problem.pillars.len()
+        );
+        Some(Self {
+            left,
+            bottom,
+            right,
+            top,
+            step,
+            n_x,
+            n_y,
+            visible,
+            words,
+            values,
+            weights,
+        })
+    }
+
+    // The grid point nearest to `p`
+    fn cell(&self, p: &Pt) -> usize {
+        let ix = ((p.x - self.left) / self.step)
+            .round()
+            .clamp(0.0, (self.n_x - 1) as f64);
+        let iy = ((p.y - self.bottom) / self.step)
+            .round()
+            .clamp(0.0, (self.n_y - 1) as f64);
+        iy as usize * self.n_x + ix as usize
+    }
+
+    fn is_visible_from(&self, cell: usize, attendee_idx: usize) -> bool {
+        self.visible[cell * self.words + attendee_idx / 64] & (1 << (attendee_idx % 64)) != 0
+    }
+
+    /// Whether no pillar stands between `p` and the attendee.
+    pub fn is_visible(&self, p: &Pt, attendee_idx: usize) -> bool {
+        self.is_visible_from(self.cell(p), attendee_idx)
+    }
+
+    /// What the instrument is worth at the grid point nearest to `p` without blocking by
+    /// musicians.
+    pub fn value(&self, instrument: usize, p: &Pt) -> f64 {
+        self.values[instrument][self.cell(p)]
+    }
+
+    /// How much the instrument loses at `to` to the pillar shadows there, compared to what it
+    /// would get from the attendees visible at `from`. Positive when moving from `from` to `to`
+    /// steps into a shadow hiding attendees who like the instrument.
+    pub fn shadow_loss(&self, problem: &Problem, instrument: usize, from: &Pt, to: &Pt) -> f64 {
+        let (from_cell, to_cell) = (self.cell(from), self.cell(to));
+        if from_cell == to_cell {
+            return 0.0;
+        }
+        problem
+            .attendees
+            .iter()
+            .enumerate()
+            .map(|(attendee_idx, attendee)| {
+                let seen_from = self.is_visible_from(from_cell, attendee_idx);
+                if seen_from == self.is_visible_from(to_cell, attendee_idx) {
+                    return 0.0;
+                }
+                let d2 = (to.x - attendee.x).powi(2) + (to.y - attendee.y).powi(2);
+                let impact = (IMPACT_SCALING_COEF * attendee.tastes[instrument] / d2).ceil();
+                if seen_from {
+                    impact
+                } else {
+                    -impact
+                }
+            })
+            .sum()
+    }
+
+    /// A point near a grid point drawn by what the instrument is worth there, or uniformly in
+    /// `UNIFORM_SHARE` of the draws and if it is worth nothing anywhere. Without the uniform draws
+    /// an instrument with more musicians than valuable points would only draw taken ones.
+    pub fn sample<R: Rng>(&self, rng: &mut R, instrument: usize) -> Pt {
+        let cell = match &self.weights[instrument] {
+            Some(weights) if !rng.gen_bool(UNIFORM_SHARE) => weights.sample(rng),
+            _ => rng.gen_range(0..self.n_x * self.n_y),
+        };
+        let half = self.step / 2.0;
+        pt(
+            (self.left + (cell % self.n_x) as f64 * self.step + rng.gen_range(-half..=half))
+                .clamp(self.left, self.right),
+            (self.bottom + (cell / self.n_x) as f64 * self.step + rng.gen_range(-half..=half))
+                .clamp(self.bottom, self.top),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use memegeom::primitive::pt;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        model::problem::{Attendee, Pillar, Problem},
+        random_solution::random_iteration,
+        scoring::{is_valid_placement, ScoringRules},
+        shadow::{shadow_map, ShadowMap},
+    };
+
+    #[test]
+    pub fn pillar_hides_attendee() {
+        let prob = Problem {
+            room_width: 400.0,
+            room_height: 400.0,
+            stage_width: 100.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0, 0],
+            attendees: vec![Attendee {
+                x: 150.0,
+                y: 300.0,
+                tastes: vec![1000.0],
+            }],
+            pillars: vec![Pillar {
+                center: vec![130.0, 200.0],
+                radius: 10.0,
+            }],
+        };
+        assert!(shadow_map(ScoringRules::Lightning, &prob).is_none());
+        let shadows = ShadowMap::new(&prob).unwrap();
+        let (shadowed, open) = (pt(120.0, 120.0), pt(180.0, 120.0));
+        assert!(!shadows.is_visible(&shadowed, 0));
+        assert!(shadows.is_visible(&open, 0));
+        assert_eq!(shadows.value(0, &shadowed), 0.0);
+        assert!(shadows.value(0, &open) > 0.0);
+        assert!(shadows.shadow_loss(&prob, 0, &open, &shadowed) > 0.0);
+        assert!(shadows.shadow_loss(&prob, 0, &shadowed, &open) < 0.0);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let solution = random_iteration(&mut rng, &prob, Some(&shadows)).unwrap();
+            assert!(is_valid_placement(&prob, &solution));
+        }
+    }
+
+    #[test]
+    pub fn sampling_goes_past_the_valuable_points() {
+        // the pillar hides the attendee from all but the leftmost and rightmost columns
+        let prob = Problem {
+            room_width: 400.0,
+            room_height: 400.0,
+            stage_width: 100.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0; 9],
+            attendees: vec![Attendee {
+                x: 150.0,
+                y: 300.0,
+                tastes: vec![1000.0],
+            }],
+            pillars: vec![Pillar {
+                center: vec![150.0, 200.0],
+                radius: 20.0,
+            }],
+        };
+        let shadows = ShadowMap::new(&prob).unwrap();
+        let n_valuable = shadows.values[0].iter().filter(|v| **v > 0.0).count();
+        assert!(n_valuable > 0 && n_valuable < prob.musicians.len());
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let n_worthless = (0..1000)
+            .filter(|_| shadows.value(0, &shadows.sample(&mut rng, 0)) <= 0.0)
+            .count();
+        assert!(n_worthless > 0);
+        for _ in 0..10 {
+            let solution = random_iteration(&mut rng, &prob, Some(&shadows)).unwrap();
+            assert!(is_valid_placement(&prob, &solution));
+        }
+    }
+}
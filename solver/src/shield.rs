@@ -0,0 +1,197 @@
+use std::time::Instant;
+
+use float_ord::FloatOrd;
+use memegeom::{
+    geom::distance::pt_pt_dist,
+    primitive::{point::Pt, pt},
+};
+use rayon::prelude::*;
+
+use crate::{
+    model::problem::{Problem, Solution},
+    progress::Progress,
+    scoring::{evaluate_exact, ScoreState, ScoringRules, BOUND_MIN_DIST},
+    slots::margin_rect,
+    stop,
+    validation::ensure_complete,
+};
+
+// Most negative attendee-musician lines a silenced musician tries to block per move
+const MAX_SHIELD_LINES: usize = 64;
+// How far from the musician a shield stands on a line, the nearest one is just past the minimum
+// distance so that rounding can't make it too close
+const SHIELD_OFFSETS: [f64; 3] = [
+    BOUND_MIN_DIST + 1.0,
+    2.0 * BOUND_MIN_DIST,
+    4.0 * BOUND_MIN_DIST,
+];
+
+/// Moves silenced musicians (volume 0) onto the lines along which the audible musicians lose
+/// points, so that they block them.
+///
+/// A silenced musician still blocks sound but plays nothing. For each of them the candidates
+/// are points on the most negative audible lines, next to the musician at the end of the line,
+/// plus its current position. The best candidate is picked by its exact gain, which already
+/// counts the positive lines it would block and the `qi` changes. Passes repeat while they help.
+pub fn shield(
+    task_id: usize,
+    rules: ScoringRules,
+    prob: &Problem,
+    solution: &Solution,
+    max_secs: u64,
+    progress: Progress,
+) -> anyhow::Result<Solution> {
+    ensure_complete(prob, solution)?;
+    let Some((left, bottom, right, top)) = margin_rect(prob) else {
+        return Ok(solution.clone());
+    };
+    let n = prob.musicians.len();
+    let silenced: Vec<usize> = (0..n).filter(|idx| solution.volumes[*idx] == 0.0).collect();
+    let mut state = ScoreState::new(rules, prob, solution);
+    let initial_score = state.score();
+    log::info!(
+        "task={task_id} shield initial score={initial_score} silenced={}",
+        silenced.len()
+    );
+
+    let start = Instant::now();
+    let mut pass = 0u64;
+    let mut improved = true;
+    'passes: while improved {
+        improved = false;
+        pass += 1;
+        for shield_idx in &silenced {
+            if start.elapsed().as_secs() > max_secs {
+                log::info!("task={task_id} pass={pass} shield time limit reached");
+                break 'passes;
+            }
+            if stop::requested() {
+                log::info!("task={task_id} pass={pass} shield stop requested");
+                break 'passes;
+            }
+            let old = state.position(*shield_idx);
+            let old_score = state.score();
+            state.remove(*shield_idx);
+            let is_free = |p: &Pt| {
+                (left..=right).contains(&p.x)
+                    && (bottom..=top).contains(&p.y)
+                    && (0..n).all(|other_idx| {
+                        !state.is_placed(other_idx)
+                            || pt_pt_dist(p, &state.position(other_idx)) >= BOUND_MIN_DIST
+                    })
+            };
+            let mut candidates: Vec<Pt> = negative_lines(&state, prob)
+                .into_iter()
+                .flat_map(|(attendee, musician)| {
+                    let d = pt_pt_dist(&attendee, &musician);
+                    SHIELD_OFFSETS
+                        .iter()
+                        .filter(move |t| **t < d)
+                        .map(move |t| {
+                            pt(
+                                musician.x + (attendee.x - musician.x) * t / d,
+                                musician.y + (attendee.y - musician.y) * t / d,
+                            )
+                        })
+                })
+                .filter(|p| is_free(p))
+                .collect();
+            candidates.push(old);
+            let (_, best) = candidates
+                .par_iter()
+                .map(|p| {
+                    let gain = state.placement_gain(*shield_idx, *p);
+                    (FloatOrd(gain), (FloatOrd(p.x), FloatOrd(p.y)))
+                })
+                .max()
+                .expect("the old position is always a candidate");
+            state.place(*shield_idx, pt(best.0 .0, best.1 .0));
+            if state.score() > old_score {
+                improved = true;
+                log::info!(
+                    "task={task_id} pass={pass} shield musician={shield_idx} moved from {old} to {}, score={}",
+                    state.position(*shield_idx),
+                    state.score()
+                );
+                progress.report(task_id, &state.to_solution(), state.score());
+            } else {
+                state.move_musician(*shield_idx, old);
+            }
+        }
+    }
+    let shielded = state.to_solution();
+    // the state is exact, but the solution is only kept if the exact evaluation agrees
+    let score = evaluate_exact(rules, prob, &shielded);
+    log::info!("task={task_id} shields improved score from {initial_score} to {score}");
+    if score < initial_score {
+        return Ok(solution.clone());
+    }
+    Ok(shielded)
+}
+
+// Attendee and musician ends of the most negative audible lines
+fn negative_lines(state: &ScoreState, prob: &Problem) -> Vec<(Pt, Pt)> {
+    let mut lines: Vec<(f64, Pt, Pt)> = Vec::new();
+    for (attendee_idx, attendee) in prob.attendees.iter().enumerate() {
+        for musician_idx in 0..prob.musicians.len() {
+            if !state.is_placed(musician_idx) {
+                continue;
+            }
+            let contribution = state.contribution(attendee_idx, musician_idx);
+            if contribution < 0.0 {
+                lines.push((
+                    contribution,
+                    pt(attendee.x, attendee.y),
+                    state.position(musician_idx),
+                ));
+            }
+        }
+    }
+    lines.sort_by_key(|(contribution, _, _)| FloatOrd(*contribution));
+    lines.truncate(MAX_SHIELD_LINES);
+    lines.into_iter().map(|(_, a, m)| (a, m)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model::problem::{Attendee, Position, Problem, Solution},
+        progress::Progress,
+        scoring::{evaluate_exact, is_valid_placement, ScoringRules},
+        shield::shield,
+    };
+
+    #[test]
+    pub fn silenced_musician_blocks_a_negative_line() {
+        let prob = Problem {
+            room_width: 300.0,
+            room_height: 300.0,
+            stage_width: 60.0,
+            stage_height: 40.0,
+            stage_bottom_left: vec![100.0, 100.0],
+            musicians: vec![0, 1],
+            attendees: vec![
+                Attendee {
+                    x: 130.0,
+                    y: 200.0,
+                    tastes: vec![1000.0, 0.0],
+                },
+                Attendee {
+                    x: 130.0,
+                    y: 20.0,
+                    tastes: vec![-1000.0, 0.0],
+                },
+            ],
+            pillars: vec![],
+        };
+        let solution = Solution {
+            placements: vec![Position::new(130.0, 125.0), Position::new(110.0, 110.0)],
+            volumes: vec![1.0, 0.0],
+        };
+        let rules = ScoringRules::Lightning;
+        assert_eq!(evaluate_exact(rules, &prob, &solution), 177778.0 - 90702.0);
+        let shielded = shield(0, rules, &prob, &solution, 10, Progress::default()).unwrap();
+        assert!(is_valid_placement(&prob, &shielded));
+        assert_eq!(evaluate_exact(rules, &prob, &shielded), 177778.0);
+    }
+}
@@ -0,0 +1,204 @@
+use memegeom::primitive::{point::Pt, pt};
+
+use crate::{model::problem::Problem, scoring::BOUND_MIN_DIST};
+
+// Hex rows are spaced slightly farther than needed, so that the diagonal distances survive
+// rounding. Along rows and in the square grid coordinates stay exact for integer stages.
+const HEX_ROW_EPS: f64 = 1e-6;
+
+/// The rectangle musicians' centers must stay in: `(left, bottom, right, top)`,
+/// `None` if the stage is too small for a single musician.
+pub fn margin_rect(problem: &Problem) -> Option<(f64, f64, f64, f64)> {
+    let left = problem.stage_bottom_left[0] + BOUND_MIN_DIST;
+    let bottom = problem.stage_bottom_left[1] + BOUND_MIN_DIST;
+    let right = problem.stage_bottom_left[0] + problem.stage_width - BOUND_MIN_DIST;
+    let top = problem.stage_bottom_left[1] + problem.stage_height - BOUND_MIN_DIST;
+    if left > right || bottom > top {
+        None
+    } else {
+        Some((left, bottom, right, top))
+    }
+}
+
+/// `start`, `start + step`, ... up to `end` inclusive
+fn line(start: f64, end: f64, step: f64) -> Vec<f64> {
+    (0..)
+        .map(|k| start + k as f64 * step)
+        .take_while(|v| *v <= end)
+        .collect()
+}
+
+// Hex lattice with horizontal rows in the `[x0, x1]×[y0, y1]` rectangle, as `(x, y)` pairs
+fn hex_rows(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<(f64, f64)> {
+    let dy = BOUND_MIN_DIST * 3f64.sqrt() / 2.0 + HEX_ROW_EPS;
+    line(y0, y1, dy)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(row, y)| {
+            let offset = if row % 2 == 0 {
+                0.0
+            } else {
+                BOUND_MIN_DIST / 2.0
+            };
+            line(x0 + offset, x1, BOUND_MIN_DIST)
+                .into_iter()
+                .map(move |x| (x, y))
+        })
+        .collect()
+}
+
+/// Hex packing of the stage, with rows along whichever side gives more slots.
+pub fn hex_slots(problem: &Problem) -> Vec<Pt> {
+    let Some((left, bottom, right, top)) = margin_rect(problem) else {
+        return vec![];
+    };
+    let horizontal = hex_rows(left, bottom, right, top);
+    let vertical = hex_rows(bottom, left, top, right);
+    if horizontal.len() >= vertical.len() {
+        horizontal.into_iter().map(|(x, y)| pt(x, y)).collect()
+    } else {
+        vertical.into_iter().map(|(y, x)| pt(x, y)).collect()
+    }
+}
+
+/// Square grid over the stage starting at its bottom left corner.
+pub fn square_slots(problem: &Problem) -> Vec<Pt> {
+    let Some((left, bottom, right, top)) = margin_rect(problem) else {
+        return vec![];
+    };
+    let xs = line(left, right, BOUND_MIN_DIST);
+    line(bottom, top, BOUND_MIN_DIST)
+        .into_iter()
+        .flat_map(|y| xs.iter().map(move |x| pt(*x, y)))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Bottom,
+    Top,
+    Left,
+    Right,
+}
+
+/// Slots along each stage edge, as close to the audience as the margin allows. Corner slots
+/// belong to the bottom and top rows.
+pub fn edge_slots(problem: &Problem) -> Vec<(Edge, Vec<Pt>)> {
+    let Some((left, bottom, right, top)) = margin_rect(problem) else {
+        return vec![];
+    };
+    let xs = line(left, right, BOUND_MIN_DIST);
+    let mut edges = vec![(Edge::Bottom, xs.iter().map(|x| pt(*x, bottom)).collect())];
+    if top - bottom >= BOUND_MIN_DIST {
+        edges.push((Edge::Top, xs.iter().map(|x| pt(*x, top)).collect()));
+    }
+    if right - left >= BOUND_MIN_DIST {
+        // without the corners, at least `BOUND_MIN_DIST` from the top row
+        let ys = line(
+            bottom + BOUND_MIN_DIST,
+            top - BOUND_MIN_DIST,
+            BOUND_MIN_DIST,
+        );
+        edges.push((Edge::Left, ys.iter().map(|y| pt(left, *y)).collect()));
+        edges.push((Edge::Right, ys.iter().map(|y| pt(right, *y)).collect()));
+    }
+    edges
+}
+
+/// One row of slots along each stage edge, see `edge_slots`.
+pub fn boundary_slots(problem: &Problem) -> Vec<Pt> {
+    edge_slots(problem)
+        .into_iter()
+        .flat_map(|(_, slots)| slots)
+        .collect()
+}
+
+/// Whichever of the hex and square lattices has more slots.
+pub fn densest_slots(problem: &Problem) -> Vec<Pt> {
+    let hex = hex_slots(problem);
+    let square = square_slots(problem);
+    if hex.len() >= square.len() {
+        hex
+    } else {
+        square
+    }
+}
+
+/// How many musicians the densest lattice above holds. Only a lower bound on what the stage
+/// can hold, as neither lattice is an optimal packing of every rectangle.
+pub fn capacity(problem: &Problem) -> usize {
+    densest_slots(problem).len()
+}
+
+/// Fails if the densest lattice can't hold all musicians, for the constructors that place them
+/// on its slots.
+pub fn ensure_capacity(problem: &Problem) -> anyhow::Result<()> {
+    let capacity = capacity(problem);
+    if capacity < problem.musicians.len() {
+        anyhow::bail!(
+            "the lattice on stage {}x{} holds {capacity} musicians, the problem has {}",
+            problem.stage_width,
+            problem.stage_height,
+            problem.musicians.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use memegeom::{geom::distance::pt_pt_dist, primitive::point::Pt};
+
+    use crate::{
+        model::problem::Problem,
+        scoring::BOUND_MIN_DIST,
+        slots::{boundary_slots, capacity, ensure_capacity, hex_slots, square_slots},
+    };
+
+    fn problem(stage_width: f64, stage_height: f64, n_musicians: usize) -> Problem {
+        Problem {
+            room_width: 500.0,
+            room_height: 500.0,
+            stage_width,
+            stage_height,
+            stage_bottom_left: vec![100.0, 50.0],
+            musicians: vec![0; n_musicians],
+            attendees: vec![],
+            pillars: vec![],
+        }
+    }
+
+    fn assert_legal(prob: &Problem, slots: &[Pt]) {
+        for (idx, p) in slots.iter().enumerate() {
+            assert!(p.x >= prob.stage_bottom_left[0] + BOUND_MIN_DIST);
+            assert!(p.x <= prob.stage_bottom_left[0] + prob.stage_width - BOUND_MIN_DIST);
+            assert!(p.y >= prob.stage_bottom_left[1] + BOUND_MIN_DIST);
+            assert!(p.y <= prob.stage_bottom_left[1] + prob.stage_height - BOUND_MIN_DIST);
+            for other in &slots[idx + 1..] {
+                assert!(pt_pt_dist(p, other) >= BOUND_MIN_DIST);
+            }
+        }
+    }
+
+    #[test]
+    pub fn slots_are_legal() {
+        for (width, height) in [(20.0, 20.0), (20.0, 45.0), (33.3, 71.7), (120.0, 95.0)] {
+            let prob = problem(width, height, 1);
+            for slots in [hex_slots(&prob), square_slots(&prob), boundary_slots(&prob)] {
+                assert!(!slots.is_empty());
+                assert_legal(&prob, &slots);
+            }
+        }
+    }
+
+    #[test]
+    pub fn crowded_stage_is_detected() {
+        // the 20x20 margin rectangle fits a 3x3 grid, the hex packing only 8
+        assert_eq!(hex_slots(&problem(40.0, 40.0, 0)).len(), 8);
+        assert_eq!(capacity(&problem(40.0, 40.0, 0)), 9);
+        assert_eq!(boundary_slots(&problem(40.0, 40.0, 0)).len(), 8);
+        assert!(ensure_capacity(&problem(40.0, 40.0, 9)).is_ok());
+        assert!(ensure_capacity(&problem(40.0, 40.0, 10)).is_err());
+        assert!(ensure_capacity(&problem(15.0, 40.0, 1)).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// The signal that asked to stop, 0 if none did
+static STOP_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    STOP_SIGNAL.store(signal, Ordering::SeqCst);
+    // A second signal kills the process as usual
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+/// Makes SIGINT and SIGTERM ask the optimizers to stop at their next check instead of killing
+/// the process, so that the best solution so far can still be written.
+///
+/// `boundary_first` and `repair` don't check: they run once without iterating and stopping them
+/// would leave no valid solution to write.
+pub fn install_handlers() -> anyhow::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            anyhow::bail!(
+                "can't handle signal {signal}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn requested() -> bool {
+    STOP_SIGNAL.load(Ordering::SeqCst) != 0
+}
+
+/// Name of the signal that asked to stop, if any
+pub fn reason() -> Option<&'static str> {
+    match STOP_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        libc::SIGINT => Some("SIGINT"),
+        libc::SIGTERM => Some("SIGTERM"),
+        _ => Some("a signal"),
+    }
+}
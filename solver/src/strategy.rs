@@ -0,0 +1,610 @@
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+
+use crate::{
+    annealing::anneal,
+    config::{Annealing, Init, LargeNeighbourhood, Optimizer, Search},
+    construct::{boundary_first, greedy},
+    lns::lns,
+    model::problem::{Problem, Solution},
+    progress::Progress,
+    random_solution::{improve_solution, random_iteration, reassign, swap_search, update_volume},
+    scoring::{evaluate_exact, ScoringRules},
+    shadow::ShadowMap,
+    shield::shield,
+    stop,
+};
+
+/// What a strategy gets to work with, one per pipeline run.
+pub struct Context<'a> {
+    pub task_id: usize,
+    pub rules: ScoringRules,
+    pub problem: &'a Problem,
+    pub rng: StdRng,
+    /// Pillar shadows of the stage, if pillars block under the rules
+    pub shadows: Option<&'a ShadowMap>,
+    /// Where optimizers report their best solutions
+    pub progress: Progress<'a>,
+}
+
+/// One stage of a pipeline such as `greedy,anneal:60s,swap,volume`.
+///
+/// Every method returns `None` if the strategy doesn't take part in that phase: the pipeline
+/// starts with `init` of its first stage, then runs `improve` of every stage in order, then
+/// `finalize` of every stage in order.
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Whether the strategy only builds solutions, so that it can only be the first stage.
+    fn is_init_only(&self) -> bool {
+        false
+    }
+
+    /// Builds a solution from scratch.
+    fn init(&self, _ctx: &mut Context) -> anyhow::Result<Option<Solution>> {
+        Ok(None)
+    }
+
+    /// Builds a new solution from the current one, it may score lower.
+    fn improve(
+        &self,
+        _ctx: &mut Context,
+        _solution: &Solution,
+    ) -> anyhow::Result<Option<Solution>> {
+        Ok(None)
+    }
+
+    /// Last changes that don't move musicians, e.g. volumes.
+    fn finalize(
+        &self,
+        _ctx: &mut Context,
+        _solution: &Solution,
+    ) -> anyhow::Result<Option<Solution>> {
+        Ok(None)
+    }
+}
+
+/// `name` or `name:arg` from a pipeline spec
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageSpec {
+    pub name: String,
+    pub arg: Option<String>,
+}
+
+/// Splits `greedy,anneal:60s,swap,volume` into stages.
+pub fn parse_pipeline(spec: &str) -> anyhow::Result<Vec<StageSpec>> {
+    let stages: Vec<StageSpec> = spec
+        .split(',')
+        .map(str::trim)
+        .filter(|stage| !stage.is_empty())
+        .map(|stage| match stage.split_once(':') {
+            Some((name, arg)) => StageSpec {
+                name: name.trim().to_string(),
+                arg: Some(arg.trim().to_string()),
+            },
+            None => StageSpec {
+                name: stage.to_string(),
+                arg: None,
+            },
+        })
+        .collect();
+    if stages.is_empty() {
+        anyhow::bail!("empty pipeline {spec:?}");
+    }
+    Ok(stages)
+}
+
+/// `90`, `90s`, `5m` or `2h`
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("bad duration {s:?}"))?;
+    let secs = match unit {
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => anyhow::bail!("bad duration unit in {s:?}, expected s, m or h"),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| anyhow::anyhow!("duration {s:?} is too long"))
+}
+
+pub struct Pipeline {
+    stages: Vec<Box<dyn Strategy>>,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Box<dyn Strategy>>) -> Self {
+        Self { stages }
+    }
+
+    pub fn describe(&self) -> String {
+        self.stages
+            .iter()
+            .map(|stage| stage.name())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Runs all stages from `start`, or from `init` of the first stage. Steps before `first_step`
+    /// are skipped, see `Checkpoint::steps`. Returns the best solution seen after any stage and
+    /// its score.
+    pub fn run(
+        &self,
+        ctx: &mut Context,
+        start: Option<&Solution>,
+        first_step: usize,
+    ) -> anyhow::Result<(Solution, f64)> {
+        let task_id = ctx.task_id;
+        let mut solution = match start {
+            Some(start) => {
+                if let Some(first) = self.stages.first().filter(|stage| stage.is_init_only()) {
+                    log::info!(
+                        "task={task_id} starting from the given solution instead of {}",
+                        first.name()
+                    );
+                }
+                start.clone()
+            }
+            None if first_step > 0 => {
+                anyhow::bail!("can't resume the pipeline at step {first_step} without a solution")
+            }
+            None => {
+                let first = self
+                    .stages
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("empty pipeline"))?;
+                first.init(ctx)?.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "pipeline can't start with {}, it doesn't build solutions",
+                        first.name()
+                    )
+                })?
+            }
+        };
+        let mut score = evaluate_exact(ctx.rules, ctx.problem, &solution);
+        log::info!("task={task_id} pipeline start score={score} step={first_step}");
+        let mut best = (solution.clone(), score);
+        ctx.progress
+            .step_done(task_id, first_step, &solution, score);
+        let stages = &self.stages;
+        let steps = [false, true]
+            .into_iter()
+            .flat_map(|finalize| stages.iter().map(move |stage| (finalize, stage)));
+        for (step, (finalize, stage)) in steps.enumerate().skip(first_step) {
+            if stop::requested() {
+                log::info!(
+                    "task={task_id} stop requested before stage {}",
+                    stage.name()
+                );
+                break;
+            }
+            let next = if finalize {
+                stage.finalize(ctx, &solution)?
+            } else {
+                stage.improve(ctx, &solution)?
+            };
+            if let Some(next) = next {
+                let next_score = evaluate_exact(ctx.rules, ctx.problem, &next);
+                log::info!(
+                    "task={task_id} stage {} score={next_score} gain={}",
+                    stage.name(),
+                    next_score - score
+                );
+                solution = next;
+                score = next_score;
+                if score > best.1 {
+                    best = (solution.clone(), score);
+                }
+            }
+            ctx.progress.step_done(task_id, step + 1, &best.0, best.1);
+        }
+        if best.1 > score {
+            log::info!(
+                "task={task_id} pipeline end score={score} is below its best {}, keeping the best",
+                best.1
+            );
+        }
+        Ok(best)
+    }
+}
+
+/// `boundary`: the stage perimeter facing the audience first, see `construct::boundary_first`.
+pub struct Boundary;
+
+impl Strategy for Boundary {
+    fn name(&self) -> String {
+        "boundary".to_string()
+    }
+
+    fn is_init_only(&self) -> bool {
+        true
+    }
+
+    fn init(&self, ctx: &mut Context) -> anyhow::Result<Option<Solution>> {
+        boundary_first(ctx.rules, ctx.problem).map(Some)
+    }
+}
+
+/// `greedy[:candidates[:time]]`: one musician at a time on the best slot, see
+/// `construct::greedy`.
+pub struct Greedy {
+    pub max_candidates: usize,
+    pub max_secs: u64,
+}
+
+impl Strategy for Greedy {
+    fn name(&self) -> String {
+        format!("greedy:{}:{}s", self.max_candidates, self.max_secs)
+    }
+
+    fn is_init_only(&self) -> bool {
+        true
+    }
+
+    fn init(&self, ctx: &mut Context) -> anyhow::Result<Option<Solution>> {
+        greedy(
+            ctx.rules,
+            ctx.problem,
+            self.max_candidates,
+            self.max_secs,
+            ctx.shadows,
+        )
+        .map(Some)
+    }
+}
+
+/// The pipeline spec of a search: its `pipeline`, or the default one.
+pub fn pipeline_spec(search: &Search) -> String {
+    search
+        .pipeline
+        .clone()
+        .unwrap_or_else(|| default_pipeline(search))
+}
+
+/// The pipeline of the `init` and `optimizer` options, used when no pipeline is given.
+pub fn default_pipeline(search: &Search) -> String {
+    let init = match search.init {
+        Init::Random => "random",
+        Init::Boundary => "boundary",
+        Init::Greedy => "greedy",
+    };
+    let optimizer = match search.optimizer {
+        Optimizer::Descent => "descent,reassign,swap",
+        Optimizer::Anneal => "anneal",
+    };
+    format!("{init},{optimizer},volume")
+}
+
+/// Strategies by their names in a pipeline spec, with defaults from `search`.
+pub fn build_pipeline(spec: &str, search: &Search) -> anyhow::Result<Pipeline> {
+    // stages count whole seconds, so a shorter budget would silently become 0
+    let secs = |arg: &Option<String>, default: u64| match arg {
+        Some(arg) => {
+            let duration = parse_duration(arg)?;
+            if !duration.is_zero() && duration.as_secs() == 0 {
+                anyhow::bail!("duration {arg:?} is under a second, stages take whole seconds");
+            }
+            Ok(duration.as_secs())
+        }
+        None => Ok(default),
+    };
+    let stages = parse_pipeline(spec)?
+        .into_iter()
+        .map(|StageSpec { name, arg }| {
+            let stage: Box<dyn Strategy> = match name.as_str() {
+                "random" => Box::new(RandomSampling {
+                    n_iters: search.rand_iters,
+                    max_secs: secs(&arg, search.rand_max_secs)?,
+                }),
+                "boundary" if arg.is_none() => Box::new(Boundary),
+                "greedy" => {
+                    let (candidates, time) = match &arg {
+                        Some(arg) => match arg.split_once(':') {
+                            Some((candidates, time)) => (Some(candidates), Some(time.to_string())),
+                            None => (Some(arg.as_str()), None),
+                        },
+                        None => (None, None),
+                    };
+                    Box::new(Greedy {
+                        max_candidates: match candidates {
+                            Some(candidates) => candidates.parse().map_err(|_| {
+                                anyhow::anyhow!("bad greedy candidates {candidates:?}")
+                            })?,
+                            None => search.greedy_candidates,
+                        },
+                        max_secs: secs(&time, search.greedy_max_secs)?,
+                    })
+                }
+                "descent" => Box::new(Descent {
+                    n_iters: search.descent_iters,
+                    max_secs: secs(&arg, search.descent_max_secs)?,
+                }),
+                "anneal" => Box::new(Anneal {
+                    params: Annealing {
+                        max_secs: secs(&arg, search.annealing.max_secs)?,
+                        ..search.annealing.clone()
+                    },
+                }),
+                "lns" => Box::new(Lns {
+                    params: LargeNeighbourhood {
+                        max_secs: secs(&arg, search.lns.max_secs)?,
+                        ..search.lns.clone()
+                    },
+                }),
+                "reassign" if arg.is_none() => Box::new(Reassign),
+                "swap" => Box::new(Swap {
+                    max_secs: secs(&arg, search.swap_max_secs)?,
+                }),
+                "volume" if arg.is_none() => Box::new(Volume),
+                "shield" => Box::new(Shield {
+                    max_secs: secs(&arg, search.shield_max_secs)?,
+                }),
+                _ => anyhow::bail!("unknown pipeline stage {name:?} with argument {arg:?}"),
+            };
+            Ok(stage)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(stage) = stages.iter().skip(1).find(|stage| stage.is_init_only()) {
+        anyhow::bail!(
+            "{} only builds solutions, it can only be the first stage of {spec:?}",
+            stage.name()
+        );
+    }
+    Ok(Pipeline::new(stages))
+}
+
+/// `random[:time]`: the best of `n_iters` uniformly random placements.
+pub struct RandomSampling {
+    pub n_iters: u64,
+    pub max_secs: u64,
+}
+
+impl Strategy for RandomSampling {
+    fn name(&self) -> String {
+        format!("random:{}s", self.max_secs)
+    }
+
+    fn is_init_only(&self) -> bool {
+        true
+    }
+
+    fn init(&self, ctx: &mut Context) -> anyhow::Result<Option<Solution>> {
+        let (task_id, rules, problem, shadows) = (ctx.task_id, ctx.rules, ctx.problem, ctx.shadows);
+        let (n_iters, max_secs) = (self.n_iters, self.max_secs);
+        let rng = &mut ctx.rng;
+        let mut best = random_iteration(rng, problem, shadows)?;
+        let mut best_score = evaluate_exact(rules, problem, &best);
+        log::info!("task={task_id} initial best_score={best_score} n_iters={n_iters}");
+        let start = Instant::now();
+        for i in 1..=n_iters {
+            let next = random_iteration(rng, problem, shadows)?;
+            let next_score = evaluate_exact(rules, problem, &next);
+            let mut is_better = false;
+            if next_score > best_score {
+                best = next;
+                best_score = next_score;
+                is_better = true;
+                ctx.progress.report(task_id, &best, best_score);
+            }
+            if is_better || i % 10000 == 0 {
+                log::info!("task={task_id} iteration={i} best_score={best_score}");
+            }
+            if start.elapsed().as_secs() > max_secs {
+                log::info!(
+                    "task={task_id} iteration={i} best_score={best_score}. Stopping due to max time"
+                );
+                break;
+            }
+            if stop::requested() {
+                log::info!(
+                    "task={task_id} iteration={i} best_score={best_score}. Stopping on request"
+                );
+                break;
+            }
+        }
+        Ok(Some(best))
+    }
+}
+
+/// `descent[:time]`: see `random_solution::improve_solution`.
+pub struct Descent {
+    pub n_iters: u64,
+    pub max_secs: u64,
+}
+
+impl Strategy for Descent {
+    fn name(&self) -> String {
+        format!("descent:{}s", self.max_secs)
+    }
+
+    fn improve(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        improve_solution(
+            ctx.task_id,
+            ctx.rules,
+            ctx.problem,
+            solution,
+            1.0,
+            self.n_iters,
+            self.max_secs,
+            ctx.shadows,
+            ctx.progress,
+        )
+        .map(Some)
+    }
+}
+
+/// `anneal[:time]`: see `annealing::anneal`.
+pub struct Anneal {
+    pub params: Annealing,
+}
+
+impl Strategy for Anneal {
+    fn name(&self) -> String {
+        format!("anneal:{}s", self.params.max_secs)
+    }
+
+    fn improve(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        anneal(
+            ctx.task_id,
+            ctx.rules,
+            ctx.problem,
+            solution,
+            &self.params,
+            ctx.progress,
+        )
+        .map(Some)
+    }
+}
+
+/// `lns[:time]`: see `lns::lns`.
+pub struct Lns {
+    pub params: LargeNeighbourhood,
+}
+
+impl Strategy for Lns {
+    fn name(&self) -> String {
+        format!("lns:{}s", self.params.max_secs)
+    }
+
+    fn improve(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        lns(
+            ctx.task_id,
+            ctx.rules,
+            ctx.problem,
+            solution,
+            &self.params,
+            &mut ctx.rng,
+            ctx.shadows,
+            ctx.progress,
+        )
+        .map(Some)
+    }
+}
+
+/// `reassign`: see `random_solution::reassign`.
+pub struct Reassign;
+
+impl Strategy for Reassign {
+    fn name(&self) -> String {
+        "reassign".to_string()
+    }
+
+    fn improve(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        reassign(ctx.task_id, ctx.rules, ctx.problem, solution).map(Some)
+    }
+}
+
+/// `swap[:time]`: see `random_solution::swap_search`.
+pub struct Swap {
+    pub max_secs: u64,
+}
+
+impl Strategy for Swap {
+    fn name(&self) -> String {
+        format!("swap:{}s", self.max_secs)
+    }
+
+    fn improve(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        swap_search(ctx.task_id, ctx.rules, ctx.problem, solution, self.max_secs).map(Some)
+    }
+}
+
+/// `volume`: see `random_solution::update_volume`.
+pub struct Volume;
+
+impl Strategy for Volume {
+    fn name(&self) -> String {
+        "volume".to_string()
+    }
+
+    fn finalize(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        update_volume(ctx.rules, ctx.problem, solution).map(Some)
+    }
+}
+
+/// `shield[:time]`: see `shield::shield`, goes after `volume` which silences the musicians.
+pub struct Shield {
+    pub max_secs: u64,
+}
+
+impl Strategy for Shield {
+    fn name(&self) -> String {
+        format!("shield:{}s", self.max_secs)
+    }
+
+    fn finalize(&self, ctx: &mut Context, solution: &Solution) -> anyhow::Result<Option<Solution>> {
+        shield(
+            ctx.task_id,
+            ctx.rules,
+            ctx.problem,
+            solution,
+            self.max_secs,
+            ctx.progress,
+        )
+        .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        config::Search,
+        strategy::{build_pipeline, parse_duration, parse_pipeline, StageSpec},
+    };
+
+    #[test]
+    pub fn pipeline_spec() {
+        assert_eq!(
+            parse_pipeline("greedy, anneal:60s,swap,volume").unwrap(),
+            vec![
+                StageSpec {
+                    name: "greedy".to_string(),
+                    arg: None
+                },
+                StageSpec {
+                    name: "anneal".to_string(),
+                    arg: Some("60s".to_string())
+                },
+                StageSpec {
+                    name: "swap".to_string(),
+                    arg: None
+                },
+                StageSpec {
+                    name: "volume".to_string(),
+                    arg: None
+                },
+            ]
+        );
+        assert!(parse_pipeline(" , ").is_err());
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
+    }
+
+    #[test]
+    pub fn init_only_stages_go_first() {
+        let search = Search::default();
+        assert!(build_pipeline("greedy,descent,volume", &search).is_ok());
+        assert!(build_pipeline("descent,greedy", &search).is_err());
+        assert!(build_pipeline("random,descent,boundary", &search).is_err());
+    }
+
+    #[test]
+    pub fn sub_second_budget_is_an_error() {
+        let search = Search::default();
+        assert!(build_pipeline("greedy,anneal:0.5s", &search).is_err());
+        assert!(build_pipeline("greedy:32:0.5s,anneal", &search).is_err());
+        assert!(build_pipeline("greedy,anneal:0s,volume", &search).is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use memegeom::geom::distance::pt_pt_dist;
+
+use crate::{
+    model::problem::{Problem, Solution},
+    scoring::{pos_to_pt, BOUND_MIN_DIST},
+};
+
+pub const MAX_VOLUME: f64 = 10.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    PlacementCount {
+        expected: usize,
+        actual: usize,
+    },
+    VolumeCount {
+        expected: usize,
+        actual: usize,
+    },
+    NanCoordinate {
+        musician: usize,
+    },
+    /// Closer than `BOUND_MIN_DIST` to the stage edge, `overshoot` is how much closer
+    OutsideStage {
+        musician: usize,
+        overshoot: f64,
+    },
+    TooClose {
+        first: usize,
+        second: usize,
+        distance: f64,
+    },
+    VolumeOutOfRange {
+        musician: usize,
+        volume: f64,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::PlacementCount { expected, actual } => {
+                write!(f, "expected {expected} placements, got {actual}")
+            }
+            ValidationError::VolumeCount { expected, actual } => {
+                write!(f, "expected {expected} volumes, got {actual}")
+            }
+            ValidationError::NanCoordinate { musician } => {
+                write!(f, "musician {musician} has NaN coordinates")
+            }
+            ValidationError::OutsideStage {
+                musician,
+                overshoot,
+            } => write!(
+                f,
+                "musician {musician} is {overshoot} too close to the stage edge"
+            ),
+            ValidationError::TooClose {
+                first,
+                second,
+                distance,
+            } => write!(
+                f,
+                "musicians {first} and {second} are {distance} apart, less than {BOUND_MIN_DIST}"
+            ),
+            ValidationError::VolumeOutOfRange { musician, volume } => write!(
+                f,
+                "musician {musician} has volume {volume} outside of 0..={MAX_VOLUME}"
+            ),
+        }
+    }
+}
+
+/// Every rule the solution breaks, empty for a valid solution.
+pub fn validate(problem: &Problem, solution: &Solution) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let n_musicians = problem.musicians.len();
+    if solution.placements.len() != n_musicians {
+        errors.push(ValidationError::PlacementCount {
+            expected: n_musicians,
+            actual: solution.placements.len(),
+        });
+    }
+    if solution.volumes.len() != n_musicians {
+        errors.push(ValidationError::VolumeCount {
+            expected: n_musicians,
+            actual: solution.volumes.len(),
+        });
+    }
+
+    let left = problem.stage_bottom_left[0] + BOUND_MIN_DIST;
+    let bottom = problem.stage_bottom_left[1] + BOUND_MIN_DIST;
+    let right = problem.stage_bottom_left[0] + problem.stage_width - BOUND_MIN_DIST;
+    let top = problem.stage_bottom_left[1] + problem.stage_height - BOUND_MIN_DIST;
+    for (musician, p) in solution.placements.iter().enumerate() {
+        if p.x.is_nan() || p.y.is_nan() {
+            errors.push(ValidationError::NanCoordinate { musician });
+            continue;
+        }
+        let overshoot = [left - p.x, p.x - right, bottom - p.y, p.y - top]
+            .into_iter()
+            .fold(0.0, f64::max);
+        if overshoot > 0.0 {
+            errors.push(ValidationError::OutsideStage {
+                musician,
+                overshoot,
+            });
+        }
+        for second in (musician + 1)..solution.placements.len() {
+            let distance = pt_pt_dist(&pos_to_pt(p), &pos_to_pt(&solution.placements[second]));
+            if distance < BOUND_MIN_DIST {
+                errors.push(ValidationError::TooClose {
+                    first: musician,
+                    second,
+                    distance,
+                });
+            }
+        }
+    }
+
+    for (musician, volume) in solution.volumes.iter().enumerate() {
+        if !(0.0..=MAX_VOLUME).contains(volume) {
+            errors.push(ValidationError::VolumeOutOfRange {
+                musician,
+                volume: *volume,
+            });
+        }
+    }
+    errors
+}
+
+/// Fails unless there is a placement and a volume for every musician, the optimizers index
+/// solutions by musician.
+pub fn ensure_complete(problem: &Problem, solution: &Solution) -> anyhow::Result<()> {
+    let n_musicians = problem.musicians.len();
+    if solution.placements.len() != n_musicians {
+        anyhow::bail!(ValidationError::PlacementCount {
+            expected: n_musicians,
+            actual: solution.placements.len(),
+        });
+    }
+    if solution.volumes.len() != n_musicians {
+        anyhow::bail!(ValidationError::VolumeCount {
+            expected: n_musicians,
+            actual: solution.volumes.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model::problem::{Position, Problem, Solution},
+        validation::{validate, ValidationError},
+    };
+
+    fn problem() -> Problem {
+        Problem {
+            room_width: 200.0,
+            room_height: 200.0,
+            stage_width: 100.0,
+            stage_height: 50.0,
+            stage_bottom_left: vec![50.0, 50.0],
+            musicians: vec![0, 1, 0],
+            attendees: vec![],
+            pillars: vec![],
+        }
+    }
+
+    #[test]
+    pub fn valid_solution() {
+        let solution = Solution::new(vec![
+            Position::new(60.0, 60.0),
+            Position::new(70.0, 60.0),
+            Position::new(140.0, 90.0),
+        ]);
+        assert_eq!(validate(&problem(), &solution), vec![]);
+    }
+
+    #[test]
+    pub fn every_violation_is_reported() {
+        let solution = Solution {
+            placements: vec![
+                Position::new(57.0, 60.0),
+                Position::new(62.0, 60.0),
+                Position::new(f64::NAN, 90.0),
+            ],
+            volumes: vec![1.0, 11.0],
+        };
+        assert_eq!(
+            validate(&problem(), &solution),
+            vec![
+                ValidationError::VolumeCount {
+                    expected: 3,
+                    actual: 2
+                },
+                ValidationError::OutsideStage {
+                    musician: 0,
+                    overshoot: 3.0
+                },
+                ValidationError::TooClose {
+                    first: 0,
+                    second: 1,
+                    distance: 5.0
+                },
+                ValidationError::NanCoordinate { musician: 2 },
+                ValidationError::VolumeOutOfRange {
+                    musician: 1,
+                    volume: 11.0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+
+use float_ord::FloatOrd;
+use memegeom::primitive::{point::Pt, seg};
+
+use crate::geometry::is_blocking_radius;
+
+// Slack for the angular and distance filters, the final decision is always `is_blocking_radius`
+const SWEEP_EPS: f64 = 1e-9;
+
+pub struct Blocker {
+    pub center: Pt,
+    pub radius: f64,
+}
+
+impl Blocker {
+    pub fn new(center: Pt, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+/// For every target, whether the segment from `origin` to it is blocked by any blocker.
+///
+/// Gives the same answers as checking `is_blocking_radius` against every blocker, but sorts
+/// targets and blockers by angle around `origin` so that only blockers whose angular span covers
+/// the target and which are not farther than the target are checked exactly:
+/// O((T + B) log(T + B)) per origin instead of O(T·B).
+///
+/// With `skip_same_index`, blocker `i` is the target `i` itself and never blocks it.
+pub fn blocked_targets(
+    origin: Pt,
+    targets: &[Pt],
+    blockers: &[Blocker],
+    skip_same_index: bool,
+) -> Vec<bool> {
+    sweep(origin, targets, blockers, skip_same_index, true)
+        .into_iter()
+        .map(|n| n > 0)
+        .collect()
+}
+
+/// Same as `blocked_targets`, but counts all blockers of every target.
+pub fn blocker_counts(
+    origin: Pt,
+    targets: &[Pt],
+    blockers: &[Blocker],
+    skip_same_index: bool,
+) -> Vec<u32> {
+    sweep(origin, targets, blockers, skip_same_index, false)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    // at equal angles intervals open before and close after the targets are queried,
+    // so the angular spans are closed
+    Open,
+    Query,
+    Close,
+}
+
+fn sweep(
+    origin: Pt,
+    targets: &[Pt],
+    blockers: &[Blocker],
+    skip_same_index: bool,
+    first_only: bool,
+) -> Vec<u32> {
+    let mut counts = vec![0; targets.len()];
+    let mut events: Vec<(FloatOrd<f64>, EventKind, usize)> =
+        Vec::with_capacity(targets.len() + 2 * blockers.len());
+    // blockers covering the origin block every direction
+    let mut surrounding = Vec::new();
+    // distance from the origin to the nearest point of each blocker
+    let mut near = vec![0.0; blockers.len()];
+    for (blocker_idx, blocker) in blockers.iter().enumerate() {
+        let dx = blocker.center.x - origin.x;
+        let dy = blocker.center.y - origin.y;
+        let d = (dx * dx + dy * dy).sqrt();
+        if d <= blocker.radius + SWEEP_EPS {
+            surrounding.push(blocker_idx);
+            continue;
+        }
+        near[blocker_idx] = d - blocker.radius - SWEEP_EPS;
+        let angle = dy.atan2(dx);
+        let half_span = (blocker.radius / d).asin() + SWEEP_EPS;
+        let (lo, hi) = (angle - half_span, angle + half_span);
+        if lo < -PI {
+            push_interval(&mut events, lo + 2.0 * PI, PI, blocker_idx);
+            push_interval(&mut events, -PI, hi, blocker_idx);
+        } else if hi > PI {
+            push_interval(&mut events, lo, PI, blocker_idx);
+            push_interval(&mut events, -PI, hi - 2.0 * PI, blocker_idx);
+        } else {
+            push_interval(&mut events, lo, hi, blocker_idx);
+        }
+    }
+    let mut dist = vec![0.0; targets.len()];
+    for (target_idx, target) in targets.iter().enumerate() {
+        let dx = target.x - origin.x;
+        let dy = target.y - origin.y;
+        dist[target_idx] = (dx * dx + dy * dy).sqrt();
+        events.push((FloatOrd(dy.atan2(dx)), EventKind::Query, target_idx));
+    }
+    events.sort_unstable();
+
+    let is_blocked_by = |target_idx: usize, blocker_idx: usize| {
+        let blocker = &blockers[blocker_idx];
+        !(skip_same_index && target_idx == blocker_idx)
+            && is_blocking_radius(
+                &seg(origin, targets[target_idx]),
+                &blocker.center,
+                blocker.radius,
+            )
+    };
+    let mut active: BTreeSet<(FloatOrd<f64>, usize)> = BTreeSet::new();
+    for (_, kind, idx) in events {
+        match kind {
+            EventKind::Open => {
+                active.insert((FloatOrd(near[idx]), idx));
+            }
+            EventKind::Close => {
+                active.remove(&(FloatOrd(near[idx]), idx));
+            }
+            EventKind::Query => {
+                let mut n = 0;
+                for &blocker_idx in &surrounding {
+                    if first_only && n > 0 {
+                        break;
+                    }
+                    if is_blocked_by(idx, blocker_idx) {
+                        n += 1;
+                    }
+                }
+                for &(FloatOrd(blocker_near), blocker_idx) in &active {
+                    if blocker_near > dist[idx] || (first_only && n > 0) {
+                        break;
+                    }
+                    if is_blocked_by(idx, blocker_idx) {
+                        n += 1;
+                    }
+                }
+                counts[idx] = n;
+            }
+        }
+    }
+    counts
+}
+
+fn push_interval(
+    events: &mut Vec<(FloatOrd<f64>, EventKind, usize)>,
+    lo: f64,
+    hi: f64,
+    blocker_idx: usize,
+) {
+    events.push((FloatOrd(lo), EventKind::Open, blocker_idx));
+    events.push((FloatOrd(hi), EventKind::Close, blocker_idx));
+}
+
+#[cfg(test)]
+mod test {
+    use memegeom::primitive::{point::Pt, pt, seg};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        geometry::{is_blocking_radius, BLOCKING_DISTANCE},
+        visibility::{blocked_targets, blocker_counts, Blocker},
+    };
+
+    fn brute_force_counts(
+        origin: Pt,
+        targets: &[Pt],
+        blockers: &[Blocker],
+        skip_same_index: bool,
+    ) -> Vec<u32> {
+        targets
+            .iter()
+            .enumerate()
+            .map(|(target_idx, target)| {
+                blockers
+                    .iter()
+                    .enumerate()
+                    .filter(|(blocker_idx, blocker)| {
+                        !(skip_same_index && *blocker_idx == target_idx)
+                            && is_blocking_radius(
+                                &seg(origin, *target),
+                                &blocker.center,
+                                blocker.radius,
+                            )
+                    })
+                    .count() as u32
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn sweep_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            // origins all around the crowd, so that spans also wrap around ±π
+            let origin = pt(rng.gen_range(-30.0..130.0), rng.gen_range(-30.0..130.0));
+            let targets: Vec<Pt> = (0..40)
+                .map(|_| pt(rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0)))
+                .collect();
+            let musicians: Vec<Blocker> = targets
+                .iter()
+                .map(|t| Blocker::new(*t, BLOCKING_DISTANCE))
+                .collect();
+            let expected = brute_force_counts(origin, &targets, &musicians, true);
+            assert_eq!(blocker_counts(origin, &targets, &musicians, true), expected);
+            assert_eq!(
+                blocked_targets(origin, &targets, &musicians, true),
+                expected.iter().map(|n| *n > 0).collect::<Vec<_>>()
+            );
+
+            let pillars: Vec<Blocker> = (0..5)
+                .map(|_| {
+                    Blocker::new(
+                        pt(rng.gen_range(-30.0..130.0), rng.gen_range(-30.0..130.0)),
+                        rng.gen_range(1.0..20.0),
+                    )
+                })
+                .collect();
+            let expected = brute_force_counts(origin, &targets, &pillars, false);
+            assert_eq!(blocker_counts(origin, &targets, &pillars, false), expected);
+        }
+    }
+
+    #[test]
+    pub fn origin_inside_blocker() {
+        let origin = pt(0.0, 0.0);
+        let targets = vec![pt(10.0, 0.0), pt(-10.0, 3.0), pt(0.0, -20.0)];
+        let pillars = vec![Blocker::new(pt(1.0, 1.0), 3.0)];
+        assert_eq!(
+            blocked_targets(origin, &targets, &pillars, false),
+            vec![true, true, true]
+        );
+    }
+}